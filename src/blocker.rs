@@ -0,0 +1,229 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Content blocking. Lists are read from the `blocklists` directory of the
+//! profile. Each line is one of:
+//!
+//! ```text
+//! # A comment
+//! 0.0.0.0 ads.example.com      hosts format: blocks these hosts, one or more
+//! tracker.example.com          blocks this domain and its subdomains
+//! *://*.example.com/ads/*      blocks URLs matching the pattern
+//! example.com/ads/             blocks URLs containing it
+//! ```
+//!
+//! Only navigations are blocked, counted per tab. Servo has no hook for
+//! subresource loads yet.
+
+use servo::ServoUrl;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::path::Path;
+
+pub struct Blocker {
+    // Exact hosts, from hosts files
+    hosts: HashSet<String>,
+    // Also block subdomains
+    domains: HashSet<String>,
+    // `*` matches anything
+    patterns: Vec<String>,
+    // Sites the user disabled blocking for
+    exceptions: HashSet<String>,
+}
+
+impl Blocker {
+    pub fn new() -> Blocker {
+        Blocker {
+            hosts: HashSet::new(),
+            domains: HashSet::new(),
+            patterns: Vec::new(),
+            exceptions: HashSet::new(),
+        }
+    }
+
+    /// Read all the files of the directory. Missing directory: nothing
+    /// is blocked.
+    pub fn load(dir: &Path) -> Result<Blocker, String> {
+        let mut blocker = Blocker::new();
+        if !dir.exists() {
+            return Ok(blocker);
+        }
+        let entries = fs::read_dir(dir)
+            .map_err(|e| format!("Can't read {}: {}", dir.display(), e))?;
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            let file = File::open(&path)
+                .map_err(|e| format!("Can't open {}: {}", path.display(), e))?;
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+                blocker.add_rule(&line);
+            }
+        }
+        info!("Content blocking: {} hosts, {} domains, {} patterns",
+              blocker.hosts.len(),
+              blocker.domains.len(),
+              blocker.patterns.len());
+        Ok(blocker)
+    }
+
+    fn add_rule(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+            return;
+        }
+        // Hosts files can end lines with a comment
+        let mut words = line.split_whitespace().take_while(|word| !word.starts_with('#'));
+        match (words.next(), words.next()) {
+            (Some(ip), Some(host)) if ip.parse::<IpAddr>().is_ok() => {
+                for host in Some(host).into_iter().chain(words) {
+                    // Hosts files also list localhost & co
+                    if host != "localhost" && host.contains('.') {
+                        self.hosts.insert(host.to_lowercase());
+                    }
+                }
+            }
+            (Some(rule), None) if rule.contains('*') => self.patterns.push(rule.to_owned()),
+            (Some(rule), None) if rule.contains('/') => {
+                self.patterns.push(format!("*{}*", rule));
+            }
+            (Some(domain), None) => {
+                self.domains.insert(domain.to_lowercase());
+            }
+            _ => warn!("Invalid blocklist rule: {}", line),
+        }
+    }
+
+    pub fn set_exceptions(&mut self, sites: &[String]) {
+        self.exceptions = sites.iter().cloned().collect();
+    }
+
+    pub fn is_blocked(&self, url: &ServoUrl) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.to_lowercase(),
+            None => return false,
+        };
+        if self.exceptions.contains(&host) {
+            return false;
+        }
+        if self.hosts.contains(&host) {
+            return true;
+        }
+        let mut domain = host.as_str();
+        loop {
+            if self.domains.contains(domain) {
+                return true;
+            }
+            match domain.find('.') {
+                Some(idx) => domain = &domain[idx + 1..],
+                None => break,
+            }
+        }
+        self.patterns
+            .iter()
+            .any(|pattern| matches(pattern, url.as_str()))
+    }
+}
+
+/// Glob match of the whole text, `*` being the only special character.
+fn matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !text.starts_with(first) {
+        return false;
+    }
+    let mut rest = &text[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        if i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    // No `*`
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocker(rules: &[&str]) -> Blocker {
+        let mut blocker = Blocker::new();
+        for rule in rules {
+            blocker.add_rule(rule);
+        }
+        blocker
+    }
+
+    fn blocks(blocker: &Blocker, url: &str) -> bool {
+        blocker.is_blocked(&ServoUrl::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "anything"));
+        assert!(matches("https://*.example.com/*", "https://ads.example.com/a.js"));
+        assert!(!matches("https://*.example.com/*", "https://example.com/a.js"));
+        assert!(matches("*/ads/*", "http://a.com/ads/b"));
+        assert!(!matches("*/ads/*", "http://a.com/ad/b"));
+        assert!(matches("a*b*c", "abc"));
+        assert!(matches("a*b*c", "a-b-b-c"));
+        assert!(!matches("a*b*c", "a-c-b"));
+        assert!(matches("exact", "exact"));
+        assert!(!matches("exact", "exactly"));
+    }
+
+    #[test]
+    fn test_add_rule() {
+        let blocker = blocker(&["# Comment",
+                                "! Adblock comment",
+                                "",
+                                "127.0.0.1 localhost",
+                                "0.0.0.0 a.example.com B.example.com c.example.com # Trackers",
+                                "::1 d.example.com",
+                                "tracker.example.org",
+                                "*://*.example.net/ads/*",
+                                "example.net/banner"]);
+        assert_eq!(blocker.hosts.len(), 4);
+        assert!(blocker.hosts.contains("b.example.com"));
+        assert!(!blocker.hosts.contains("localhost"));
+        assert!(!blocker.hosts.contains("#"));
+        assert_eq!(blocker.domains.len(), 1);
+        assert_eq!(blocker.patterns,
+                   vec!["*://*.example.net/ads/*".to_owned(), "*example.net/banner*".to_owned()]);
+    }
+
+    #[test]
+    fn test_is_blocked() {
+        let mut blocker = blocker(&["0.0.0.0 ads.example.com",
+                                    "tracker.example.org",
+                                    "*://*.example.net/ads/*",
+                                    "example.net/banner"]);
+        assert!(blocks(&blocker, "https://ads.example.com/"));
+        assert!(blocks(&blocker, "https://ADS.example.com/"));
+        assert!(!blocks(&blocker, "https://sub.ads.example.com/"));
+        assert!(!blocks(&blocker, "https://example.com/"));
+        assert!(blocks(&blocker, "https://tracker.example.org/"));
+        assert!(blocks(&blocker, "https://a.b.tracker.example.org/x"));
+        assert!(!blocks(&blocker, "https://nottracker.example.org/"));
+        assert!(blocks(&blocker, "https://www.example.net/ads/a.js"));
+        assert!(!blocks(&blocker, "https://www.example.net/news/"));
+        assert!(blocks(&blocker, "https://example.net/banner.png"));
+        assert!(blocks(&blocker, "http://example.net/banner?x=1"));
+        assert!(!blocks(&blocker, "about:blank"));
+
+        blocker.set_exceptions(&["tracker.example.org".to_owned()]);
+        assert!(!blocks(&blocker, "https://tracker.example.org/"));
+        assert!(blocks(&blocker, "https://ads.example.com/"));
+    }
+}
@@ -0,0 +1,99 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+extern crate png;
+
+use self::png::HasParameters;
+use servo::EventLoopWaker;
+use std::fmt;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use traits::view::gl;
+
+/// RGBA pixels, rows from top to bottom.
+#[derive(Clone, PartialEq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Frame({}x{})", self.width, self.height)
+    }
+}
+
+impl Frame {
+    /// Read back a rectangle of the current framebuffer. `y` is from the top.
+    pub fn read(gl: &gl::Gl,
+                framebuffer_height: u32,
+                x: u32,
+                y: u32,
+                width: u32,
+                height: u32)
+                -> Frame {
+        // GL origin is bottom-left
+        let gl_y = framebuffer_height - y - height;
+        let pixels = gl.read_pixels(x as i32,
+                                    gl_y as i32,
+                                    width as i32,
+                                    height as i32,
+                                    gl::RGBA,
+                                    gl::UNSIGNED_BYTE);
+
+        // And rows are bottom to top
+        let mut flipped = Vec::with_capacity(pixels.len());
+        for row in pixels.chunks((width * 4) as usize).rev() {
+            flipped.extend_from_slice(row);
+        }
+
+        Frame {
+            width,
+            height,
+            pixels: flipped,
+        }
+    }
+
+    pub fn write_png(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Wakes up the event loop once `timeout` is over, for runs that would
+/// otherwise wait forever on a page that never finishes loading.
+pub struct Deadline {
+    passed: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    pub fn new(timeout: Duration, waker: Box<EventLoopWaker + Send>) -> Result<Deadline, String> {
+        let passed = Arc::new(AtomicBool::new(false));
+        let thread_passed = passed.clone();
+        thread::Builder::new()
+            .name("Deadline".to_owned())
+            .spawn(move || {
+                       thread::sleep(timeout);
+                       thread_passed.store(true, Ordering::SeqCst);
+                       waker.wake();
+                   })
+            .map_err(|e| format!("Can't start the timeout: {}", e))?;
+        Ok(Deadline { passed })
+    }
+
+    pub fn has_passed(&self) -> bool {
+        self.passed.load(Ordering::SeqCst)
+    }
+}
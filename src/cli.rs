@@ -0,0 +1,522 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use log::LogLevelFilter;
+use logs::{self, LogFilter};
+use servo::ServoUrl;
+use shell_pages;
+use user_agent;
+use std::path::{Path, PathBuf};
+
+// Taken as is from the command line. Anything else is a file or a host.
+const URL_SCHEMES: &'static [&'static str] = &["http", "https", "file", "data", "about"];
+
+pub const DEFAULT_SCREENSHOT_TIMEOUT: u64 = 30;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Value {
+    // --flag
+    None,
+    // --flag VALUE or --flag=VALUE
+    Required(&'static str),
+}
+
+struct Flag {
+    name: &'static str,
+    value: Value,
+    help: &'static str,
+}
+
+const FLAGS: &'static [Flag] = &[Flag {
+                                      name: "--help",
+                                      value: Value::None,
+                                      help: "Print this message and exit",
+                                  },
+                                  Flag {
+                                      name: "--version",
+                                      value: Value::None,
+                                      help: "Print the version and exit",
+                                  },
+                                  Flag {
+                                      name: "--headless",
+                                      value: Value::None,
+                                      help: "Render offscreen, without any window",
+                                  },
+                                  Flag {
+                                      name: "--window-size",
+                                      value: Value::Required("WIDTHxHEIGHT"),
+                                      help: "Initial size of the window",
+                                  },
+                                  Flag {
+                                      name: "--user-agent",
+                                      value: Value::Required("STRING"),
+                                      help: "User agent, or a preset: desktop, android, ios",
+                                  },
+                                  Flag {
+                                      name: "--resources-path",
+                                      value: Value::Required("PATH"),
+                                      help: "Servo's resources directory",
+                                  },
+                                  Flag {
+                                      name: "--profile",
+                                      value: Value::Required("NAME"),
+                                      help: "Use the named profile (default: default)",
+                                  },
+                                  Flag {
+                                      name: "--kiosk",
+                                      value: Value::None,
+                                      help: "Fullscreen, without shortcuts. See `kiosk` in config",
+                                  },
+                                  Flag {
+                                      name: "--private",
+                                      value: Value::None,
+                                      help: "Open a private window. Nothing is written to disk",
+                                  },
+                                  Flag {
+                                      name: "--profile-dir",
+                                      value: Value::Required("PATH"),
+                                      help: "Use this directory as the profile",
+                                  },
+                                  Flag {
+                                      name: "--list-profiles",
+                                      value: Value::None,
+                                      help: "Print the named profiles and exit",
+                                  },
+                                  Flag {
+                                      name: "--create-profile",
+                                      value: Value::Required("NAME"),
+                                      help: "Create a named profile and exit",
+                                  },
+                                  Flag {
+                                      name: "--delete-profile",
+                                      value: Value::Required("NAME"),
+                                      help: "Delete a named profile and exit",
+                                  },
+                                  Flag {
+                                      name: "--pref",
+                                      value: Value::Required("KEY=VALUE"),
+                                      help: "Set a Servo preference. Can be repeated",
+                                  },
+                                  Flag {
+                                      name: "--log-level",
+                                      value: Value::Required("LEVEL"),
+                                      help: "off, error, warn, info, debug or trace",
+                                  },
+                                  Flag {
+                                      name: "--log-filter",
+                                      value: Value::Required("FILTERS"),
+                                      help: "Levels per target, like warn,servoshell=debug",
+                                  },
+                                  Flag {
+                                      name: "--log-capacity",
+                                      value: Value::Required("COUNT"),
+                                      help: "Logs kept in memory (default: 1000)",
+                                  },
+                                  Flag {
+                                      name: "--log-file",
+                                      value: Value::Required("FILE"),
+                                      help: "Also write the logs to this file, rotated",
+                                  },
+                                  Flag {
+                                      name: "--log-json",
+                                      value: Value::Required("FILE"),
+                                      help: "Also write the logs as JSON lines. - for stdout",
+                                  },
+                                  Flag {
+                                      name: "--screenshot",
+                                      value: Value::Required("FILE"),
+                                      help: "Save the page as a PNG once loaded, and exit",
+                                  },
+                                  Flag {
+                                      name: "--screenshot-timeout",
+                                      value: Value::Required("SECONDS"),
+                                      help: "Fail the screenshot after this long (default: 30)",
+                                  },
+                                  Flag {
+                                      name: "--reftest",
+                                      value: Value::Required("MANIFEST"),
+                                      help: "Run the reftests of the manifest, and exit",
+                                  },
+                                  Flag {
+                                      name: "--reftest-output",
+                                      value: Value::Required("DIR"),
+                                      help: "Where failed reftests go (default: reftest-results)",
+                                  },
+                                  Flag {
+                                      name: "--control-socket",
+                                      value: Value::Required("PATH"),
+                                      help: "Accept automation clients on this Unix socket",
+                                  }];
+
+pub enum ProfileCommand {
+    List,
+    Create(String),
+    Delete(String),
+}
+
+pub struct Options {
+    pub help: bool,
+    pub version: bool,
+    pub headless: bool,
+    // One tab each. Files and host names are turned into URLs.
+    pub urls: Vec<String>,
+    pub window_size: Option<(u32, u32)>,
+    pub user_agent: Option<String>,
+    pub resources_path: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub profile_dir: Option<PathBuf>,
+    pub private: bool,
+    pub kiosk: bool,
+    pub profile_command: Option<ProfileCommand>,
+    pub prefs: Vec<(String, String)>,
+    pub log_level: LogLevelFilter,
+    // Overrides SERVOSHELL_LOG
+    pub log_filter: Option<String>,
+    pub log_capacity: usize,
+    pub log_file: Option<PathBuf>,
+    pub log_json: Option<PathBuf>,
+    pub screenshot: Option<PathBuf>,
+    pub screenshot_timeout: u64,
+    pub reftest: Option<PathBuf>,
+    pub reftest_output: Option<PathBuf>,
+    pub control_socket: Option<PathBuf>,
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            help: false,
+            version: false,
+            headless: false,
+            urls: Vec::new(),
+            window_size: None,
+            user_agent: None,
+            resources_path: None,
+            profile: None,
+            profile_dir: None,
+            private: false,
+            kiosk: false,
+            profile_command: None,
+            prefs: Vec::new(),
+            log_level: LogLevelFilter::Info,
+            log_filter: None,
+            log_capacity: logs::DEFAULT_CAPACITY,
+            log_file: None,
+            log_json: None,
+            screenshot: None,
+            screenshot_timeout: DEFAULT_SCREENSHOT_TIMEOUT,
+            reftest: None,
+            reftest_output: None,
+            control_socket: None,
+        }
+    }
+}
+
+/// Parse the arguments, without the executable name.
+pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut options = Options::new();
+    let mut args = args.peekable();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("-") {
+            options.urls.push(parse_url(&arg)?);
+            continue;
+        }
+
+        let (name, inline_value) = match arg.find('=') {
+            Some(idx) => (arg[..idx].to_owned(), Some(arg[idx + 1..].to_owned())),
+            None => (arg.clone(), None),
+        };
+
+        let flag = match FLAGS.iter().find(|f| f.name == name) {
+            Some(flag) => flag,
+            None => {
+                return Err(match suggest(&name) {
+                               Some(other) => {
+                                   format!("Unknown option `{}`. Did you mean `{}`?", name, other)
+                               }
+                               None => format!("Unknown option `{}`", name),
+                           })
+            }
+        };
+
+        let value = match (flag.value, inline_value) {
+            (Value::None, Some(_)) => {
+                return Err(format!("Option `{}` doesn't take a value", name));
+            }
+            (Value::None, None) => None,
+            (Value::Required(_), Some(value)) => Some(value),
+            (Value::Required(what), None) => {
+                let is_value = args.peek().map_or(false, |next| !next.starts_with("--"));
+                if !is_value {
+                    return Err(format!("Option `{}` requires a value: {} {}", name, name, what));
+                }
+                args.next()
+            }
+        };
+
+        apply(&mut options, flag.name, value)?;
+    }
+
+    if options.reftest.is_some() && options.window_size.is_some() {
+        return Err("`--reftest` renders at a fixed size, without `--window-size`".to_owned());
+    }
+    // The reftest results are printed to stdout
+    let json_to_stdout = options.log_json.as_ref().map_or(false, |p| p == Path::new("-"));
+    if options.reftest.is_some() && json_to_stdout {
+        return Err("`--reftest` prints to stdout, use `--log-json FILE`".to_owned());
+    }
+
+    Ok(options)
+}
+
+fn apply(options: &mut Options, name: &str, value: Option<String>) -> Result<(), String> {
+    let invalid = |value: &str, expected: &str| {
+        format!("Invalid value `{}` for `{}`: expected {}", value, name, expected)
+    };
+    match (name, value) {
+        ("--help", _) => options.help = true,
+        ("--version", _) => options.version = true,
+        ("--headless", _) => options.headless = true,
+        ("--window-size", Some(value)) => {
+            let size = {
+                let mut parts = value.splitn(2, 'x').map(|p| p.parse::<u32>().ok());
+                match (parts.next(), parts.next()) {
+                    (Some(Some(w)), Some(Some(h))) if w > 0 && h > 0 => Some((w, h)),
+                    _ => None,
+                }
+            };
+            options.window_size =
+                Some(size.ok_or_else(|| invalid(&value, "WIDTHxHEIGHT, like 1024x768"))?);
+        }
+        ("--user-agent", Some(value)) => options.user_agent = Some(user_agent::resolve(&value)),
+        ("--resources-path", Some(value)) => {
+            let path = PathBuf::from(&value);
+            if !path.is_dir() {
+                return Err(invalid(&value, "an existing directory"));
+            }
+            options.resources_path = Some(path);
+        }
+        ("--profile", Some(value)) => {
+            if options.profile_dir.is_some() {
+                return Err("`--profile` and `--profile-dir` can't be used together".to_owned());
+            }
+            options.profile = Some(value);
+        }
+        ("--profile-dir", Some(value)) => {
+            if options.profile.is_some() {
+                return Err("`--profile` and `--profile-dir` can't be used together".to_owned());
+            }
+            options.profile_dir = Some(PathBuf::from(value));
+        }
+        ("--private", _) => options.private = true,
+        ("--kiosk", _) => options.kiosk = true,
+        ("--list-profiles", _) => options.profile_command = Some(ProfileCommand::List),
+        ("--create-profile", Some(value)) => {
+            options.profile_command = Some(ProfileCommand::Create(value))
+        }
+        ("--delete-profile", Some(value)) => {
+            options.profile_command = Some(ProfileCommand::Delete(value))
+        }
+        ("--pref", Some(value)) => {
+            let pref = {
+                let mut parts = value.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(v)) if !key.is_empty() => {
+                        Some((key.to_owned(), v.to_owned()))
+                    }
+                    _ => None,
+                }
+            };
+            options
+                .prefs
+                .push(pref.ok_or_else(|| invalid(&value, "KEY=VALUE"))?);
+        }
+        ("--log-level", Some(value)) => {
+            options.log_level = value
+                .parse()
+                .map_err(|_| invalid(&value, "off, error, warn, info, debug or trace"))?;
+        }
+        ("--log-filter", Some(value)) => {
+            LogFilter::parse(&value, LogLevelFilter::Info)?;
+            options.log_filter = Some(value);
+        }
+        ("--log-capacity", Some(value)) => {
+            options.log_capacity = match value.parse() {
+                Ok(count) if count > 0 => count,
+                _ => return Err(invalid(&value, "a positive number")),
+            };
+        }
+        ("--log-file", Some(value)) => options.log_file = Some(PathBuf::from(value)),
+        ("--log-json", Some(value)) => options.log_json = Some(PathBuf::from(value)),
+        ("--screenshot", Some(value)) => options.screenshot = Some(PathBuf::from(value)),
+        ("--screenshot-timeout", Some(value)) => {
+            options.screenshot_timeout = match value.parse() {
+                Ok(seconds) if seconds > 0 => seconds,
+                _ => return Err(invalid(&value, "a positive number of seconds")),
+            };
+        }
+        ("--reftest", Some(value)) => options.reftest = Some(PathBuf::from(value)),
+        ("--reftest-output", Some(value)) => options.reftest_output = Some(PathBuf::from(value)),
+        ("--control-socket", Some(value)) => options.control_socket = Some(PathBuf::from(value)),
+        (name, _) => unreachable!("Unhandled option {}", name),
+    }
+    Ok(())
+}
+
+/// `servo.org` is `http://servo.org/`, and `index.html` a file URL if the
+/// file exists.
+fn parse_url(arg: &str) -> Result<String, String> {
+    let path = Path::new(arg);
+    if path.exists() {
+        return path.canonicalize()
+                   .ok()
+                   .and_then(|path| ServoUrl::from_file_path(path).ok())
+                   .map(|url| url.as_str().to_owned())
+                   .ok_or_else(|| format!("Can't make a URL from the path `{}`", arg));
+    }
+    let known = |url: &ServoUrl| {
+        URL_SCHEMES.contains(&url.scheme()) || url.scheme() == shell_pages::SCHEME
+    };
+    // `localhost:8000` parses, with `localhost` as the scheme
+    match ServoUrl::parse(arg) {
+        Ok(ref url) if known(url) => return Ok(url.as_str().to_owned()),
+        _ => {}
+    }
+    let is_host = !arg.is_empty() && !arg.contains(char::is_whitespace) &&
+                  (arg.contains('.') || arg.starts_with("localhost"));
+    match ServoUrl::parse(&format!("http://{}", arg)) {
+        Ok(ref url) if is_host => Ok(url.as_str().to_owned()),
+        _ => Err(format!("`{}` is not a URL, a file or a host name", arg)),
+    }
+}
+
+/// The closest known option, if any is close enough.
+fn suggest(name: &str) -> Option<&'static str> {
+    FLAGS
+        .iter()
+        .map(|flag| (distance(name, flag.name), flag.name))
+        .filter(|&(d, _)| d <= 3)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                previous
+            } else {
+                1 + previous.min(row[j]).min(row[j + 1])
+            };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+pub fn usage(program: &str) -> String {
+    let mut usage = format!("Usage: {} [OPTIONS] [URL...]\n\nOptions:\n", program);
+    for flag in FLAGS {
+        let name = match flag.value {
+            Value::None => flag.name.to_owned(),
+            Value::Required(what) => format!("{} {}", flag.name, what),
+        };
+        usage.push_str(&format!("  {:<32}{}\n", name, flag.help));
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn parse_args(args: &[&str]) -> Result<Options, String> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let options = parse_args(&["--headless",
+                                   "--window-size",
+                                   "800x600",
+                                   "--log-level=debug",
+                                   "--pref",
+                                   "js.enabled=false",
+                                   "https://servo.org"])
+                .unwrap();
+        assert!(options.headless);
+        assert_eq!(options.window_size, Some((800, 600)));
+        assert_eq!(options.log_level, LogLevelFilter::Debug);
+        assert_eq!(options.prefs,
+                   vec![("js.enabled".to_owned(), "false".to_owned())]);
+        assert_eq!(options.urls, vec!["https://servo.org/".to_owned()]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_args(&["--window-size"]).is_err());
+        assert!(parse_args(&["--window-size", "800"]).is_err());
+        assert!(parse_args(&["--headless=yes"]).is_err());
+        assert!(parse_args(&["--pref", "=true"]).is_err());
+        assert!(parse_args(&["--profile", "a", "--profile-dir", "b"]).is_err());
+        assert!(parse_args(&["--reftest", "a.list", "--window-size", "800x600"]).is_err());
+        assert!(parse_args(&["--reftest", "a.list", "--log-json", "-"]).is_err());
+        assert!(parse_args(&["--reftest", "a.list", "--log-json", "logs.json"]).is_ok());
+        assert!(parse_args(&["--webdriver"]).is_err());
+        assert!(parse_args(&["--devtools=6000"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_option() {
+        assert_eq!(parse_args(&["--headles"]).err().unwrap(),
+                   "Unknown option `--headles`. Did you mean `--headless`?");
+        assert_eq!(parse_args(&["--nothing-like-it"]).err().unwrap(),
+                   "Unknown option `--nothing-like-it`");
+    }
+
+    #[test]
+    fn test_parse_urls() {
+        assert_eq!(parse_url("https://servo.org").unwrap(), "https://servo.org/");
+        assert_eq!(parse_url("servo.org").unwrap(), "http://servo.org/");
+        assert_eq!(parse_url("localhost:8000").unwrap(), "http://localhost:8000/");
+        assert_eq!(parse_url("about:blank").unwrap(), "about:blank");
+        assert_eq!(parse_url("servoshell://history").unwrap(), "servoshell://history");
+        assert!(parse_url("not a url").is_err());
+        assert!(parse_url("servo").is_err());
+        assert!(parse_url("").is_err());
+    }
+
+    #[test]
+    fn test_parse_file_urls() {
+        let exe = env::current_exe().unwrap();
+        let url = parse_url(exe.to_str().unwrap()).unwrap();
+        assert!(url.starts_with("file:///"));
+    }
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("abc", "abc"), 0);
+        assert_eq!(distance("abc", ""), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("--headles", "--headless"), 1);
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(suggest("--verison"), Some("--version"));
+        assert_eq!(suggest("--profil"), Some("--profile"));
+        assert_eq!(suggest("--log-levle"), Some("--log-level"));
+        assert_eq!(suggest("--something-else"), None);
+    }
+}
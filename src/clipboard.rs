@@ -0,0 +1,95 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+extern crate clipboard;
+
+use self::clipboard::{ClipboardContext, ClipboardProvider};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub trait Clipboard {
+    fn get_contents(&self) -> Result<String, String>;
+    fn set_contents(&self, contents: String) -> Result<(), String>;
+    /// Servo has no clipboard hook for embedders: pages read and write
+    /// the system clipboard directly, and this provider is only used by
+    /// the shell (URL bar, copy commands). This tells Servo whether the
+    /// system clipboard is there.
+    fn is_native(&self) -> bool;
+}
+
+pub struct NativeClipboard(RefCell<ClipboardContext>);
+
+impl Clipboard for NativeClipboard {
+    fn get_contents(&self) -> Result<String, String> {
+        self.0
+            .borrow_mut()
+            .get_contents()
+            .map_err(|e| e.to_string())
+    }
+
+    fn set_contents(&self, contents: String) -> Result<(), String> {
+        self.0
+            .borrow_mut()
+            .set_contents(contents)
+            .map_err(|e| e.to_string())
+    }
+
+    fn is_native(&self) -> bool {
+        true
+    }
+}
+
+/// Clipboard that lives in the shell only. Used when the system
+/// clipboard is not reachable (no display). Pages can't use it.
+pub struct MemoryClipboard(RefCell<Option<String>>);
+
+impl MemoryClipboard {
+    pub fn new() -> MemoryClipboard {
+        MemoryClipboard(RefCell::new(None))
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get_contents(&self) -> Result<String, String> {
+        self.0
+            .borrow()
+            .clone()
+            .ok_or("Clipboard is empty".to_owned())
+    }
+
+    fn set_contents(&self, contents: String) -> Result<(), String> {
+        *self.0.borrow_mut() = Some(contents);
+        Ok(())
+    }
+
+    fn is_native(&self) -> bool {
+        false
+    }
+}
+
+/// The system clipboard if available, an in-memory one otherwise.
+pub fn new_clipboard() -> Rc<Clipboard> {
+    match ClipboardContext::new() {
+        Ok(ctx) => Rc::new(NativeClipboard(RefCell::new(ctx))),
+        Err(e) => {
+            warn!("Can't access system clipboard ({}). Using in-memory clipboard.", e);
+            Rc::new(MemoryClipboard::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clipboard, MemoryClipboard};
+
+    #[test]
+    fn memory_clipboard_keeps_the_last_contents() {
+        let clipboard = MemoryClipboard::new();
+        assert!(clipboard.get_contents().is_err());
+        clipboard.set_contents("a".to_owned()).unwrap();
+        clipboard.set_contents("b".to_owned()).unwrap();
+        assert_eq!(clipboard.get_contents(), Ok("b".to_owned()));
+        assert!(!clipboard.is_native());
+    }
+}
@@ -0,0 +1,167 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! User settings, read from `config.json` in the profile directory. Every
+//! field is optional:
+//!
+//! ```json
+//! {
+//!   "home_url": "https://servo.org",
+//!   "search_url": "https://duckduckgo.com/html/?q={}",
+//!   "window_size": { "width": 1024, "height": 768 },
+//!   "scroll_line_height": 38.0,
+//!   "tab_title": { "template": "{selected} {title} {loading}", "max_length": 15 },
+//!   "kiosk": { "exit_chord": "Ctrl+Alt+Shift+Q", "allowed_origins": [], "idle_timeout": 0 }
+//! }
+//! ```
+
+use kiosk::KioskConfig;
+use serde_json;
+use servo::{EventLoopWaker, ServoUrl};
+use state::TabTitleFormat;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// How often the file is checked for changes
+const POLL_INTERVAL_MS: u64 = 1000;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // None: the built-in home page
+    pub home_url: Option<String>,
+    // `{}` is replaced by the search terms
+    pub search_url: String,
+    pub window_size: WindowSize,
+    // In pixels, for mouse wheels that scroll by lines
+    pub scroll_line_height: f32,
+    pub tab_title: TabTitleFormat,
+    // Only used with --kiosk
+    pub kiosk: KioskConfig,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            home_url: None,
+            search_url: "https://duckduckgo.com/html/?q={}".to_owned(),
+            window_size: WindowSize {
+                width: 1024,
+                height: 768,
+            },
+            scroll_line_height: 38.0,
+            tab_title: TabTitleFormat::default(),
+            kiosk: KioskConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// The settings the types can't enforce.
+    fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if let Some(ref url) = self.home_url {
+            if ServoUrl::parse(url).is_err() {
+                errors.push(format!("home_url: `{}` is not a valid URL", url));
+            }
+        }
+        if !self.search_url.contains("{}") {
+            errors.push("search_url: must contain `{}`, replaced by the search terms".to_owned());
+        } else if ServoUrl::parse(&self.search_url.replace("{}", "servo")).is_err() {
+            errors.push(format!("search_url: `{}` is not a valid URL", self.search_url));
+        }
+        if self.window_size.width == 0 || self.window_size.height == 0 {
+            errors.push("window_size: width and height must be greater than 0".to_owned());
+        }
+        if !(self.scroll_line_height > 0.0) || !self.scroll_line_height.is_finite() {
+            errors.push("scroll_line_height: must be a positive number".to_owned());
+        }
+        if !self.tab_title.template.contains("{title}") {
+            errors.push("tab_title.template: must contain `{title}`".to_owned());
+        }
+        if self.tab_title.max_length == 0 {
+            errors.push("tab_title.max_length: must be greater than 0".to_owned());
+        }
+        if let Err(err) = self.kiosk
+               .validate(self.home_url.as_ref().map(|url| url.as_str())) {
+            errors.push(format!("kiosk: {}", err));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Search URL for these terms.
+    pub fn search(&self, terms: &str) -> String {
+        self.search_url.replace("{}", terms)
+    }
+}
+
+/// Defaults if there's no config file.
+pub fn load(path: &Path) -> Result<Config, String> {
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let file = File::open(path)
+        .map_err(|e| format!("Can't open {}: {}", path.display(), e))?;
+    let config: Config = serde_json::from_reader(file)
+        .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+    config
+        .validate()
+        .map_err(|e| format!("Invalid {}:\n{}", path.display(), e))?;
+    Ok(config)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Polls the config file, and wakes up the event loop when it changes.
+pub struct ConfigWatcher {
+    changed: Arc<AtomicBool>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf, waker: Box<EventLoopWaker + Send>) -> Option<ConfigWatcher> {
+        let changed = Arc::new(AtomicBool::new(false));
+        let thread_changed = changed.clone();
+        let spawned = thread::Builder::new()
+            .name("ConfigWatcher".to_owned())
+            .spawn(move || {
+                let mut last = modified(&path);
+                loop {
+                    thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+                    let current = modified(&path);
+                    if current != last {
+                        last = current;
+                        thread_changed.store(true, Ordering::SeqCst);
+                        waker.wake();
+                    }
+                }
+            });
+        if let Err(err) = spawned {
+            warn!("Can't watch the config file: {}", err);
+            return None;
+        }
+        Some(ConfigWatcher { changed })
+    }
+
+    /// True once after each change.
+    pub fn has_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+}
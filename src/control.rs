@@ -0,0 +1,383 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Automation server. Clients connect to a Unix domain socket and exchange
+//! newline-delimited JSON messages:
+//!
+//! ```text
+//! > {"type": "Command", "command": {"Load": "https://servo.org"}}
+//! < {"type": "Response", "result": null}
+//! > {"type": "ViewEvent", "event": {"MouseMoved": [10, 20]}}
+//! > {"type": "GetWindowState"}
+//! > {"type": "Subscribe", "events": ["LoadEnd", "TitleChanged"]}
+//! < {"type": "Event", "name": "LoadEnd", "data": {"browser": ...}}
+//! < {"type": "Error", "message": "..."}
+//! ```
+//!
+//! Connections are handled on their own threads. Requests are queued, the
+//! event loop is woken up and pulls them with `get_requests`. Messages are
+//! queued too, and written by the connection's thread: a client that
+//! doesn't read them is dropped once its queue is full.
+
+use serde_json::{self, Map, Value};
+use servo::{EventLoopWaker, ServoEvent};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender, TrySendError};
+use std::thread;
+use traits::view::ViewEvent;
+use traits::window::WindowCommand;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum Request {
+    Command { command: WindowCommand },
+    ViewEvent { event: ViewEvent },
+    GetWindowState,
+    Subscribe { events: Vec<String> },
+    Unsubscribe { events: Vec<String> },
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum Message<'a> {
+    Response { result: Value },
+    Error { message: String },
+    Event { name: &'a str, data: Value },
+}
+
+pub type ClientId = usize;
+
+// Messages not written yet, per client
+const QUEUE_SIZE: usize = 1000;
+
+struct Client {
+    id: ClientId,
+    messages: SyncSender<String>,
+    subscriptions: Vec<String>,
+}
+
+type Clients = Arc<Mutex<Vec<Client>>>;
+
+pub struct ControlServer {
+    path: PathBuf,
+    clients: Clients,
+    requests: Receiver<(ClientId, Request)>,
+}
+
+impl ControlServer {
+    #[cfg(unix)]
+    pub fn new(path: &Path, waker: Box<EventLoopWaker + Send>) -> Result<ControlServer, String> {
+        use std::os::unix::net::UnixListener;
+
+        use std::os::unix::fs::FileTypeExt;
+
+        // Left behind by a previous instance. Anything else is not ours
+        // to remove.
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(format!("{:?} exists and is not a socket", path));
+            }
+            fs::remove_file(path).map_err(|e| format!("Can't remove {:?}: {}", path, e))?;
+        }
+        let listener = UnixListener::bind(path)
+            .map_err(|e| format!("Can't bind {:?}: {}", path, e))?;
+
+        let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+        let (sender, requests) = channel();
+
+        let thread_clients = clients.clone();
+        thread::Builder::new()
+            .name("ControlServer".to_owned())
+            .spawn(move || {
+                for (id, stream) in listener.incoming().enumerate() {
+                    let streams = stream.and_then(|s| s.try_clone().map(|w| (s, w)));
+                    let (stream, writer) = match streams {
+                        Ok(streams) => streams,
+                        Err(err) => {
+                            warn!("Control connection failed: {}", err);
+                            continue;
+                        }
+                    };
+                    let (messages, queue) = sync_channel(QUEUE_SIZE);
+                    thread_clients
+                        .lock()
+                        .unwrap()
+                        .push(Client {
+                                  id,
+                                  messages,
+                                  subscriptions: Vec::new(),
+                              });
+                    let clients = thread_clients.clone();
+                    let sender = sender.clone();
+                    let waker = waker.clone();
+                    thread::spawn(move || write_messages(writer, queue));
+                    thread::spawn(move || {
+                        read_requests(id, BufReader::new(stream), clients, sender, waker)
+                    });
+                }
+            })
+            .map_err(|e| format!("Can't start control server: {}", e))?;
+
+        info!("Control server listening on {:?}", path);
+
+        Ok(ControlServer {
+               path: path.to_owned(),
+               clients,
+               requests,
+           })
+    }
+
+    #[cfg(not(unix))]
+    pub fn new(_path: &Path, _waker: Box<EventLoopWaker + Send>) -> Result<ControlServer, String> {
+        Err("The control server requires Unix domain sockets".to_owned())
+    }
+
+    pub fn get_requests(&self) -> Vec<(ClientId, Request)> {
+        self.requests.try_iter().collect()
+    }
+
+    pub fn respond(&self, client: ClientId, result: Result<Value, String>) {
+        let message = match result {
+            Ok(result) => Message::Response { result },
+            Err(message) => Message::Error { message },
+        };
+        send(&self.clients, Some(client), None, &message);
+    }
+
+    /// Forward the event to the clients that subscribed to it.
+    pub fn notify(&self, event: &ServoEvent) {
+        if let Some((name, data)) = describe(event) {
+            send(&self.clients,
+                 None,
+                 Some(name),
+                 &Message::Event { name, data });
+        }
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+fn read_requests<R: BufRead>(id: ClientId,
+                             reader: R,
+                             clients: Clients,
+                             sender: Sender<(ClientId, Request)>,
+                             waker: Box<EventLoopWaker + Send>) {
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            // Subscriptions don't need the event loop
+            Ok(Request::Subscribe { events }) => {
+                update_subscriptions(&clients, id, |subs| subs.extend(events));
+                send(&clients, Some(id), None, &Message::Response { result: Value::Null });
+            }
+            Ok(Request::Unsubscribe { events }) => {
+                update_subscriptions(&clients, id, |subs| subs.retain(|s| !events.contains(s)));
+                send(&clients, Some(id), None, &Message::Response { result: Value::Null });
+            }
+            Ok(request) => {
+                if sender.send((id, request)).is_err() {
+                    break;
+                }
+                waker.wake();
+            }
+            Err(err) => {
+                let message = format!("Invalid request: {}", err);
+                send(&clients, Some(id), None, &Message::Error { message });
+            }
+        }
+    }
+    clients.lock().unwrap().retain(|c| c.id != id);
+}
+
+/// Until the client is gone, or can't be written to.
+fn write_messages<W: Write>(mut writer: W, queue: Receiver<String>) {
+    for line in queue {
+        if writer.write_all(line.as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+fn update_subscriptions<F>(clients: &Clients, id: ClientId, f: F)
+    where F: FnOnce(&mut Vec<String>)
+{
+    if let Some(client) = clients.lock().unwrap().iter_mut().find(|c| c.id == id) {
+        f(&mut client.subscriptions);
+    }
+}
+
+/// Send to one client, or to the clients subscribed to `event`. Never
+/// blocks. Clients that can't be written to, or that don't keep up, are
+/// dropped.
+fn send(clients: &Clients, to: Option<ClientId>, event: Option<&str>, message: &Message) {
+    let mut line = match serde_json::to_string(message) {
+        Ok(line) => line,
+        Err(err) => {
+            warn!("Can't serialize control message: {}", err);
+            return;
+        }
+    };
+    line.push('\n');
+    let mut clients = clients.lock().unwrap();
+    let mut gone = Vec::new();
+    for client in clients.iter_mut() {
+        let recipient = match (to, event) {
+            (Some(id), _) => client.id == id,
+            (None, Some(event)) => client.subscriptions.iter().any(|s| s == event),
+            (None, None) => false,
+        };
+        if !recipient {
+            continue;
+        }
+        match client.messages.try_send(line.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                warn!("Control client {} doesn't read its messages, dropping it", client.id);
+                gone.push(client.id);
+            }
+            Err(TrySendError::Disconnected(_)) => gone.push(client.id),
+        }
+    }
+    clients.retain(|c| !gone.contains(&c.id));
+}
+
+fn describe(event: &ServoEvent) -> Option<(&'static str, Value)> {
+    let mut data = Map::new();
+    let name = {
+        let mut insert = |key: &str, value: Value| { data.insert(key.to_owned(), value); };
+        match *event {
+            ServoEvent::LoadStart(id) => {
+                insert("browser", serde_json::to_value(id).ok()?);
+                "LoadStart"
+            }
+            ServoEvent::LoadEnd(id) => {
+                insert("browser", serde_json::to_value(id).ok()?);
+                "LoadEnd"
+            }
+            ServoEvent::HeadParsed(id) => {
+                insert("browser", serde_json::to_value(id).ok()?);
+                "HeadParsed"
+            }
+            ServoEvent::TitleChanged(id, ref title) => {
+                insert("browser", serde_json::to_value(id).ok()?);
+                insert("title", serde_json::to_value(title).ok()?);
+                "TitleChanged"
+            }
+            ServoEvent::StatusChanged(ref status) => {
+                insert("status", serde_json::to_value(status).ok()?);
+                "StatusChanged"
+            }
+            ServoEvent::HistoryChanged(id, ref entries, current) => {
+                insert("browser", serde_json::to_value(id).ok()?);
+                insert("url", Value::String(entries[current].url.to_string()));
+                "HistoryChanged"
+            }
+            ServoEvent::ScreenshotTaken(ref path, ref result) => {
+                insert("path", Value::String(path.display().to_string()));
+                insert("error", serde_json::to_value(result.as_ref().err()).ok()?);
+                "ScreenshotTaken"
+            }
+            ServoEvent::NavigationBlocked(id, ref url) => {
+                insert("browser", serde_json::to_value(id).ok()?);
+                insert("url", Value::String(url.to_string()));
+                "NavigationBlocked"
+            }
+            _ => return None,
+        }
+    };
+    Some((name, Value::Object(data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_parse_requests() {
+        let parse = |json| serde_json::from_str::<Request>(json);
+        match parse(r#"{"type": "Command", "command": {"Load": "https://servo.org"}}"#) {
+            Ok(Request::Command { command: WindowCommand::Load(ref url) }) => {
+                assert_eq!(url, "https://servo.org")
+            }
+            other => panic!("{:?}", other),
+        }
+        match parse(r#"{"type": "Command", "command": "Reload"}"#) {
+            Ok(Request::Command { command: WindowCommand::Reload }) => {}
+            other => panic!("{:?}", other),
+        }
+        match parse(r#"{"type": "ViewEvent", "event": {"MouseMoved": [10, 20]}}"#) {
+            Ok(Request::ViewEvent { event: ViewEvent::MouseMoved(10, 20) }) => {}
+            other => panic!("{:?}", other),
+        }
+        match parse(r#"{"type": "Subscribe", "events": ["LoadEnd"]}"#) {
+            Ok(Request::Subscribe { events }) => assert_eq!(events, vec!["LoadEnd".to_owned()]),
+            other => panic!("{:?}", other),
+        }
+        assert!(parse(r#"{"type": "GetWindowState"}"#).is_ok());
+        assert!(parse(r#"{"type": "Shutdown"}"#).is_err());
+        assert!(parse(r#"{"type": "Command", "command": "Explode"}"#).is_err());
+        assert!(parse(r#"{"command": "Reload"}"#).is_err());
+    }
+
+    #[test]
+    fn test_describe() {
+        let (name, data) = describe(&ServoEvent::StatusChanged(Some("Done".to_owned()))).unwrap();
+        assert_eq!(name, "StatusChanged");
+        assert_eq!(data, json(r#"{"status": "Done"}"#));
+
+        let event = ServoEvent::ScreenshotTaken(PathBuf::from("/tmp/a.png"), Err("No".to_owned()));
+        let (name, data) = describe(&event).unwrap();
+        assert_eq!(name, "ScreenshotTaken");
+        assert_eq!(data, json(r#"{"path": "/tmp/a.png", "error": "No"}"#));
+
+        let event = ServoEvent::ScreenshotTaken(PathBuf::from("/tmp/a.png"), Ok(()));
+        assert_eq!(describe(&event).unwrap().1["error"], Value::Null);
+
+        assert!(describe(&ServoEvent::SetFullScreenState(true)).is_none());
+    }
+
+    fn client(id: ClientId, messages: SyncSender<String>) -> Client {
+        Client {
+            id,
+            messages,
+            subscriptions: vec!["LoadEnd".to_owned()],
+        }
+    }
+
+    #[test]
+    fn test_send() {
+        let (messages, queue) = sync_channel(1);
+        let (slow_messages, _slow_queue) = sync_channel(0);
+        let clients: Clients = Arc::new(Mutex::new(vec![client(0, messages),
+                                                        client(1, slow_messages)]));
+        let message = Message::Response { result: Value::Null };
+        send(&clients, None, Some("LoadStart"), &message);
+        assert!(queue.try_recv().is_err());
+        send(&clients, None, Some("LoadEnd"), &message);
+        assert_eq!(queue.try_recv().unwrap(), "{\"type\":\"Response\",\"result\":null}\n");
+        // Never blocks on a client that doesn't read
+        let ids: Vec<ClientId> = clients.lock().unwrap().iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![0]);
+        drop(queue);
+        send(&clients, Some(0), None, &message);
+        assert!(clients.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Kiosk mode (`--kiosk`): fullscreen, no shell shortcuts but the exit
+//! chord, navigation restricted to some origins, and back to the home page
+//! when nobody touched the screen for a while. Set in `config.json`:
+//!
+//! ```json
+//! "kiosk": {
+//!   "exit_chord": "Ctrl+Alt+Shift+Q",
+//!   "allowed_origins": ["https://servo.org"],
+//!   "idle_timeout": 300
+//! }
+//! ```
+//!
+//! With allowed origins, `home_url` must be one of them. Shell pages
+//! (servoshell://) are never shown.
+
+use serde_json::{self, Value};
+use servo::{EventLoopWaker, ServoUrl};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use traits::view::Key;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KioskConfig {
+    // Modifiers (Ctrl, Alt, Shift, Super) and a key name (Q, F12, Escape…)
+    pub exit_chord: String,
+    // Like `https://example.com`. Empty: any origin
+    pub allowed_origins: Vec<String>,
+    // In seconds. 0: never go back to the home page
+    pub idle_timeout: u64,
+}
+
+impl Default for KioskConfig {
+    fn default() -> KioskConfig {
+        KioskConfig {
+            exit_chord: "Ctrl+Alt+Shift+Q".to_owned(),
+            allowed_origins: Vec::new(),
+            idle_timeout: 0,
+        }
+    }
+}
+
+impl KioskConfig {
+    /// The home page, where the kiosk starts and goes back to, must be
+    /// one of the allowed origins.
+    pub fn validate(&self, home_url: Option<&str>) -> Result<(), String> {
+        Chord::parse(&self.exit_chord)?;
+        for origin in &self.allowed_origins {
+            let valid = ServoUrl::parse(origin)
+                .map(|url| url.origin().ascii_serialization() == *origin)
+                .unwrap_or(false);
+            if !valid {
+                return Err(format!("`{}` is not an origin, like https://example.com", origin));
+            }
+        }
+        if self.allowed_origins.is_empty() {
+            return Ok(());
+        }
+        match home_url.map(ServoUrl::parse) {
+            Some(Ok(ref url)) if self.allows(url) => Ok(()),
+            Some(_) => Err("home_url must be in allowed_origins".to_owned()),
+            None => Err("set home_url to a page of allowed_origins".to_owned()),
+        }
+    }
+
+    pub fn allows(&self, url: &ServoUrl) -> bool {
+        self.allowed_origins.is_empty() ||
+        self.allowed_origins
+            .iter()
+            .any(|origin| *origin == url.origin().ascii_serialization())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub logo: bool,
+    pub key: Key,
+}
+
+impl Chord {
+    pub fn parse(chord: &str) -> Result<Chord, String> {
+        let mut parsed = Chord {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+            key: Key::Escape,
+        };
+        let mut parts: Vec<&str> = chord.split('+').map(|part| part.trim()).collect();
+        let key = parts.pop().unwrap_or("");
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => parsed.ctrl = true,
+                "alt" => parsed.alt = true,
+                "shift" => parsed.shift = true,
+                "super" | "cmd" | "logo" => parsed.logo = true,
+                _ => return Err(format!("exit_chord: unknown modifier `{}`", modifier)),
+            }
+        }
+        // Same names as Servo's keys
+        parsed.key = serde_json::from_value(Value::String(key.to_owned()))
+            .map_err(|_| format!("exit_chord: unknown key `{}`", key))?;
+        Ok(parsed)
+    }
+}
+
+/// Wakes up the event loop once nothing happened for `timeout`.
+pub struct IdleWatcher {
+    last_activity: Arc<Mutex<Instant>>,
+    expired: Arc<AtomicBool>,
+}
+
+impl IdleWatcher {
+    pub fn new(timeout: Duration, waker: Box<EventLoopWaker + Send>) -> Option<IdleWatcher> {
+        let last_activity = Arc::new(Mutex::new(Instant::now()));
+        let expired = Arc::new(AtomicBool::new(false));
+        let thread_last_activity = last_activity.clone();
+        let thread_expired = expired.clone();
+        let spawned = thread::Builder::new()
+            .name("IdleWatcher".to_owned())
+            .spawn(move || loop {
+                       let idle = thread_last_activity.lock().unwrap().elapsed();
+                       if idle >= timeout {
+                           *thread_last_activity.lock().unwrap() = Instant::now();
+                           thread_expired.store(true, Ordering::SeqCst);
+                           waker.wake();
+                           thread::sleep(timeout);
+                       } else {
+                           thread::sleep(timeout - idle);
+                       }
+                   });
+        if let Err(err) = spawned {
+            warn!("Can't watch for idleness: {}", err);
+            return None;
+        }
+        Some(IdleWatcher {
+                 last_activity,
+                 expired,
+             })
+    }
+
+    /// The user did something.
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    /// True once each time the timeout expires.
+    pub fn has_expired(&self) -> bool {
+        self.expired.swap(false, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KioskConfig;
+    use servo::ServoUrl;
+
+    fn kiosk(origins: &[&str]) -> KioskConfig {
+        KioskConfig {
+            allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+            ..KioskConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(kiosk(&[]).validate(None).is_ok());
+        assert!(kiosk(&["https://servo.org"])
+                    .validate(Some("https://servo.org/about"))
+                    .is_ok());
+        assert!(kiosk(&["https://servo.org"]).validate(None).is_err());
+        assert!(kiosk(&["https://servo.org"])
+                    .validate(Some("https://example.com"))
+                    .is_err());
+        assert!(kiosk(&["https://servo.org/"]).validate(None).is_err());
+    }
+
+    #[test]
+    fn test_allows() {
+        let url = |url| ServoUrl::parse(url).unwrap();
+        assert!(kiosk(&[]).allows(&url("https://example.com")));
+        let kiosk = kiosk(&["https://servo.org"]);
+        assert!(kiosk.allows(&url("https://servo.org/a?b#c")));
+        assert!(!kiosk.allows(&url("http://servo.org")));
+        assert!(!kiosk.allows(&url("https://servo.org.example.com")));
+    }
+}
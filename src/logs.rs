@@ -2,54 +2,484 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+//! Shell logs. The last ones are kept in memory for the UI, and can also
+//! be written to a file, rotated once it gets too big.
+//!
+//! Levels can be set per target, like `warn,servoshell::servo=debug`: a
+//! bare level applies to everything else, and the longest matching target
+//! wins. Taken from `--log-filter`, or the `SERVOSHELL_LOG` environment
+//! variable.
+//!
+//! With `--log-json`, each log is also written as a JSON line:
+//!
+//! ```json
+//! {"level":"WARN","target":"servoshell","message":"…","timestamp":1508000000000,
+//!  "browser":…,"url":"https://servo.org/"}
+//! ```
+//!
+//! `browser` and `url` are only there for logs made while the shell
+//! handles something about a tab, see `set_context`.
+//!
+//! Private windows only keep logs in memory: main doesn't set a file or
+//! JSON sink for them.
+
 use log::*;
+use serde_json::{self, Map, Value};
+use servo::{BrowserId, EventLoopWaker};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, ThreadId};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const LOG_ENV: &'static str = "SERVOSHELL_LOG";
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+// Log files are rotated past this size
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+// log.1 … log.3
+const MAX_FILE_BACKUPS: usize = 3;
 
+#[derive(Clone)]
 pub struct ShellLog {
     pub level: LogLevel,
     pub target: String,
     pub message: String,
+    // Milliseconds since the epoch
+    pub timestamp: u64,
+    pub browser: Option<BrowserId>,
+    pub url: Option<String>,
+}
+
+impl ShellLog {
+    pub fn to_json(&self) -> String {
+        let mut entry = Map::new();
+        entry.insert("level".to_owned(), Value::String(self.level.to_string()));
+        entry.insert("target".to_owned(), Value::String(self.target.clone()));
+        entry.insert("message".to_owned(), Value::String(self.message.clone()));
+        entry.insert("timestamp".to_owned(), Value::from(self.timestamp));
+        if let Some(browser) = self.browser {
+            entry.insert("browser".to_owned(),
+                         serde_json::to_value(browser).unwrap_or(Value::Null));
+        }
+        if let Some(ref url) = self.url {
+            entry.insert("url".to_owned(), Value::String(url.clone()));
+        }
+        Value::Object(entry).to_string()
+    }
+}
+
+/// The tab the shell is busy with.
+#[derive(Clone)]
+pub struct LogContext {
+    pub browser: BrowserId,
+    pub url: Option<String>,
+}
+
+// Only the main thread knows about tabs
+thread_local!(static CONTEXT: RefCell<Option<LogContext>> = RefCell::new(None));
+
+/// Logs made on this thread until the next call are about this tab.
+/// Returns the previous one.
+pub fn set_context(context: Option<LogContext>) -> Option<LogContext> {
+    CONTEXT.with(|c| mem::replace(&mut *c.borrow_mut(), context))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogFilter {
+    default: LogLevelFilter,
+    targets: Vec<(String, LogLevelFilter)>,
 }
 
-pub struct ShellLogs(Mutex<Vec<ShellLog>>);
+impl LogFilter {
+    pub fn new(default: LogLevelFilter) -> LogFilter {
+        LogFilter {
+            default,
+            targets: Vec::new(),
+        }
+    }
+
+    /// `LEVEL` and `TARGET=LEVEL`, comma separated.
+    pub fn parse(spec: &str, default: LogLevelFilter) -> Result<LogFilter, String> {
+        let mut filter = LogFilter::new(default);
+        for directive in spec.split(',').map(|d| d.trim()).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            let (target, level) = match (parts.next(), parts.next()) {
+                (Some(level), None) => (None, level),
+                (Some(target), Some(level)) if !target.is_empty() => (Some(target), level),
+                _ => return Err(format!("Invalid log filter `{}`", directive)),
+            };
+            let level = level
+                .parse()
+                .map_err(|_| format!("Invalid log level `{}` in `{}`", level, directive))?;
+            match target {
+                Some(target) => filter.targets.push((target.to_owned(), level)),
+                None => filter.default = level,
+            }
+        }
+        Ok(filter)
+    }
+
+    fn level(&self, target: &str) -> LogLevelFilter {
+        self.targets
+            .iter()
+            .filter(|&&(ref prefix, _)| {
+                        target == prefix ||
+                        (target.starts_with(prefix.as_str()) &&
+                         target[prefix.len()..].starts_with("::"))
+                    })
+            .max_by_key(|&&(ref prefix, _)| prefix.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LogLevelFilter {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, |max, level| if level > max { level } else { max })
+    }
+}
+
+struct LogBuffer {
+    entries: VecDeque<ShellLog>,
+    capacity: usize,
+    // Not pulled by get_logs yet
+    unread: usize,
+    // Ever added
+    total: u64,
+}
+
+/// The last `capacity` logs. Older ones are dropped.
+pub struct ShellLogs {
+    buffer: Mutex<LogBuffer>,
+    // And the thread of the event loop
+    waker: Mutex<Option<(Box<EventLoopWaker + Send>, ThreadId)>>,
+    watched: AtomicBool,
+    // Woken up since the last call to watch
+    wake_pending: AtomicBool,
+}
 
 impl ShellLogs {
+    fn new(capacity: usize) -> ShellLogs {
+        ShellLogs {
+            buffer: Mutex::new(LogBuffer {
+                                   entries: VecDeque::with_capacity(capacity),
+                                   capacity,
+                                   unread: 0,
+                                   total: 0,
+                               }),
+            waker: Mutex::new(None),
+            watched: AtomicBool::new(false),
+            wake_pending: AtomicBool::new(false),
+        }
+    }
+
+    fn push(&self, log: ShellLog) {
+        {
+            let mut buffer = self.buffer.lock().unwrap();
+            if buffer.capacity == 0 {
+                return;
+            }
+            if buffer.entries.len() == buffer.capacity {
+                buffer.entries.pop_front();
+            }
+            buffer.entries.push_back(log);
+            buffer.unread = (buffer.unread + 1).min(buffer.capacity);
+            buffer.total += 1;
+        }
+        if self.watched.load(Ordering::SeqCst) {
+            self.wake();
+        }
+    }
+
+    /// Logs made on the event loop's thread are seen at the end of the
+    /// iteration. Others wake it up, once until the next call to `watch`.
+    fn wake(&self) {
+        if let Some((ref waker, thread)) = *self.waker.lock().unwrap() {
+            if thread != thread::current().id() && !self.wake_pending.swap(true, Ordering::SeqCst) {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Called from the event loop's thread.
+    pub fn set_waker(&self, waker: Box<EventLoopWaker + Send>) {
+        *self.waker.lock().unwrap() = Some((waker, thread::current().id()));
+    }
+
+    /// Whether new logs wake up the event loop, like while a page shows
+    /// them. Call it before reading the logs.
+    pub fn watch(&self, watched: bool) {
+        self.watched.store(watched, Ordering::SeqCst);
+        self.wake_pending.store(false, Ordering::SeqCst);
+    }
+
+    /// The logs added since the last call.
     pub fn get_logs(&self) -> Vec<ShellLog> {
-        let mut vec = self.0.lock().unwrap();
-        let res = vec.drain(..).collect();
-        res
+        let mut buffer = self.buffer.lock().unwrap();
+        let skip = buffer.entries.len() - buffer.unread;
+        buffer.unread = 0;
+        buffer.entries.iter().skip(skip).cloned().collect()
+    }
+
+    /// How many logs were ever added. Tells if there are new ones.
+    pub fn total(&self) -> u64 {
+        self.buffer.lock().unwrap().total
+    }
+
+    /// All the logs still in the buffer.
+    pub fn peek(&self) -> Vec<ShellLog> {
+        self.buffer.lock().unwrap().entries.iter().cloned().collect()
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    // Rotated past this size
+    max_size: u64,
+}
+
+impl LogFile {
+    fn open(path: &Path) -> Result<LogFile, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Can't open {}: {}", path.display(), e))?;
+        let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(LogFile {
+               path: path.to_owned(),
+               file,
+               size,
+               max_size: MAX_FILE_SIZE,
+           })
+    }
+
+    fn backup(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn write(&mut self, line: &str) {
+        if self.size + line.len() as u64 > self.max_size {
+            if let Err(err) = self.rotate() {
+                // Not logged, that would come back here
+                eprintln!("Can't rotate {}: {}", self.path.display(), err);
+            }
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+
+    fn rotate(&mut self) -> Result<(), String> {
+        for index in (1..MAX_FILE_BACKUPS).rev() {
+            let from = self.backup(index);
+            if from.exists() {
+                fs::rename(&from, self.backup(index + 1)).map_err(|e| e.to_string())?;
+            }
+        }
+        fs::rename(&self.path, self.backup(1)).map_err(|e| e.to_string())?;
+        let reopened = LogFile::open(&self.path)?;
+        *self = LogFile { max_size: self.max_size, ..reopened };
+        Ok(())
     }
 }
 
-pub struct Logger(Arc<ShellLogs>);
+pub struct LogOptions {
+    pub filter: LogFilter,
+    pub capacity: usize,
+    pub file: Option<PathBuf>,
+    // JSON lines. `-` for stdout
+    pub json: Option<PathBuf>,
+}
+
+pub struct Logger {
+    logs: Arc<ShellLogs>,
+    filter: LogFilter,
+    file: Option<Mutex<LogFile>>,
+    json: Option<Mutex<Box<Write + Send>>>,
+}
 
 impl Logger {
-    pub fn init() -> Arc<ShellLogs> {
-        let mut rv = None;
+    pub fn init(options: LogOptions) -> Result<Arc<ShellLogs>, String> {
+        let file = match options.file {
+            Some(ref path) => Some(Mutex::new(LogFile::open(path)?)),
+            None => None,
+        };
+        let json: Option<Box<Write + Send>> = match options.json {
+            Some(ref path) if path.as_os_str() == "-" => Some(Box::new(io::stdout())),
+            Some(ref path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("Can't open {}: {}", path.display(), e))?;
+                Some(Box::new(file))
+            }
+            None => None,
+        };
+        let logs = Arc::new(ShellLogs::new(options.capacity));
+        let logger = Logger {
+            logs: logs.clone(),
+            filter: options.filter,
+            file,
+            json: json.map(Mutex::new),
+        };
         set_logger(|max_log_level| {
-                       max_log_level.set(LogLevelFilter::Info);
-                       let logs = Arc::new(ShellLogs(Mutex::new(Vec::new())));
-                       rv = Some(logs.clone());
-                       Box::new(Logger(logs))
+                       max_log_level.set(logger.filter.max_level());
+                       Box::new(logger)
                    })
-                .unwrap();
-        rv.unwrap()
+                .map_err(|e| e.to_string())?;
+        Ok(logs)
     }
 }
 
 impl Log for Logger {
     fn enabled(&self, metadata: &LogMetadata) -> bool {
-        metadata.level() <= LogLevel::Info
+        metadata.level() <= self.filter.level(metadata.target())
     }
 
     fn log(&self, record: &LogRecord) {
         if self.enabled(record.metadata()) {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() * 1000 + d.subsec_nanos() as u64 / 1_000_000)
+                .unwrap_or(0);
+            let context = CONTEXT.with(|c| c.borrow().clone());
             let log = ShellLog {
                 level: record.level(),
                 message: format!("{}", record.args()),
                 target: format!("{}", record.target()),
+                timestamp,
+                browser: context.as_ref().map(|c| c.browser),
+                url: context.and_then(|c| c.url),
             };
-            (self.0).0.lock().unwrap().push(log);
+            if let Some(ref file) = self.file {
+                let line = format!("{} - {}: {}\n", log.level, log.target, log.message);
+                file.lock().unwrap().write(&line);
+            }
+            if let Some(ref json) = self.json {
+                let mut json = json.lock().unwrap();
+                writeln!(json, "{}", log.to_json()).ok();
+                json.flush().ok();
+            }
+            self.logs.push(log);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use storage;
+
+    fn log(message: &str) -> ShellLog {
+        ShellLog {
+            level: LogLevel::Info,
+            target: "servoshell".to_owned(),
+            message: message.to_owned(),
+            timestamp: 0,
+            browser: None,
+            url: None,
+        }
+    }
+
+    fn messages(logs: Vec<ShellLog>) -> Vec<String> {
+        logs.into_iter().map(|log| log.message).collect()
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let filter = LogFilter::parse("debug, servoshell::servo=trace,script=off",
+                                      LogLevelFilter::Warn)
+                .unwrap();
+        assert_eq!(filter.default, LogLevelFilter::Debug);
+        assert_eq!(filter.targets,
+                   vec![("servoshell::servo".to_owned(), LogLevelFilter::Trace),
+                        ("script".to_owned(), LogLevelFilter::Off)]);
+        assert_eq!(LogFilter::parse("", LogLevelFilter::Warn).unwrap(),
+                   LogFilter::new(LogLevelFilter::Warn));
+        assert!(LogFilter::parse("loud", LogLevelFilter::Warn).is_err());
+        assert!(LogFilter::parse("servo=loud", LogLevelFilter::Warn).is_err());
+        assert!(LogFilter::parse("=debug", LogLevelFilter::Warn).is_err());
+    }
+
+    #[test]
+    fn test_filter_level() {
+        let filter = LogFilter::parse("warn,servoshell=info,servoshell::servo=trace",
+                                      LogLevelFilter::Error)
+                .unwrap();
+        assert_eq!(filter.level("servoshell"), LogLevelFilter::Info);
+        assert_eq!(filter.level("servoshell::logs"), LogLevelFilter::Info);
+        // The longest matching target wins
+        assert_eq!(filter.level("servoshell::servo"), LogLevelFilter::Trace);
+        assert_eq!(filter.level("servoshell::servo::x"), LogLevelFilter::Trace);
+        // Whole path segments only
+        assert_eq!(filter.level("servoshell::servos"), LogLevelFilter::Info);
+        assert_eq!(filter.level("servoshellx"), LogLevelFilter::Warn);
+        assert_eq!(filter.level("script"), LogLevelFilter::Warn);
+        assert_eq!(filter.max_level(), LogLevelFilter::Trace);
+    }
+
+    #[test]
+    fn test_buffer() {
+        let logs = ShellLogs::new(3);
+        for message in &["1", "2", "3", "4", "5"] {
+            logs.push(log(message));
+        }
+        assert_eq!(logs.total(), 5);
+        assert_eq!(messages(logs.peek()), vec!["3", "4", "5"]);
+        // Unread ones can't be more than the capacity
+        assert_eq!(messages(logs.get_logs()), vec!["3", "4", "5"]);
+        assert!(logs.get_logs().is_empty());
+        logs.push(log("6"));
+        assert_eq!(messages(logs.get_logs()), vec!["6"]);
+        assert_eq!(messages(logs.peek()), vec!["4", "5", "6"]);
+
+        let logs = ShellLogs::new(0);
+        logs.push(log("1"));
+        assert_eq!(logs.total(), 0);
+        assert!(logs.get_logs().is_empty());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = storage::create_temp_dir("servoshell-test-logs-").unwrap();
+        let path = dir.join("log");
+        let read = |path: &Path| {
+            let mut content = String::new();
+            File::open(path)
+                .and_then(|mut file| file.read_to_string(&mut content))
+                .map(|_| content)
+                .ok()
+        };
+        let mut file = LogFile::open(&path).unwrap();
+        file.max_size = 4;
+        for line in &["a\n", "b\n", "c\n", "d\n", "e\n", "f\n"] {
+            file.write(line);
         }
+        assert_eq!(read(&path).unwrap(), "e\nf\n");
+        assert_eq!(read(&file.backup(1)).unwrap(), "c\nd\n");
+        assert_eq!(read(&file.backup(2)).unwrap(), "a\nb\n");
+        assert_eq!(read(&file.backup(3)), None);
+        file.write("g\n");
+        file.write("h\n");
+        assert_eq!(read(&file.backup(3)).unwrap(), "a\nb\n");
+        file.write("i\n");
+        file.write("j\n");
+        // The oldest one is dropped
+        assert_eq!(read(&file.backup(3)).unwrap(), "c\nd\n");
+        assert_eq!(read(&file.backup(4)), None);
+        fs::remove_dir_all(&dir).ok();
     }
 }
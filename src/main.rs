@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 #![feature(box_syntax)]
+#![feature(getpid)]
 #![feature(link_args)]
 #![feature(slice_patterns)]
 
@@ -15,7 +16,7 @@ extern crate treediff;
 #[macro_use]
 extern crate log;
 
-#[cfg(all(not(feature = "force-glutin"), target_os = "macos"))]
+#[cfg(unix)]
 extern crate libc;
 #[cfg(all(not(feature = "force-glutin"), target_os = "macos"))]
 extern crate cocoa;
@@ -28,6 +29,7 @@ extern crate glutin;
 #[cfg(any(feature = "force-glutin", not(target_os = "macos")))]
 extern crate tinyfiledialogs;
 
+extern crate osmesa_sys;
 
 #[cfg(target_os = "windows")]
 extern crate winapi;
@@ -35,25 +37,51 @@ extern crate winapi;
 extern crate user32;
 #[cfg(target_os = "windows")]
 extern crate gdi32;
+#[cfg(target_os = "windows")]
+extern crate kernel32;
 
 extern crate open;
 
 mod traits;
+mod blocker;
 mod platform;
 mod servo;
 mod state;
 mod logs;
+mod palette;
+mod clipboard;
+mod storage;
+mod capture;
+mod reftest;
+mod control;
+mod cli;
+mod config;
+mod profile;
+mod kiosk;
+mod shell_pages;
+mod user_agent;
 
 use platform::App;
-use servo::{Servo, ServoEvent, ServoUrl, WebRenderDebugOption};
-use state::{AppState, State, WindowState};
+use blocker::Blocker;
+use config::{Config, ConfigWatcher};
+use control::{ControlServer, Request};
+use capture::Deadline;
+use kiosk::IdleWatcher;
+use profile::{PrivateStorage, Profile};
+use servo::{BrowserId, Servo, ServoEvent, ServoUrl, WebRenderDebugOption};
+use shell_pages::{PageCommand, PageContext, Route, ShellPages, ShellRequest};
+use state::{AppState, Bookmark, BrowserState, ChangeType, CommandPaletteState,
+            ContextMenuItem, ContextMenuState, HistoryEntry, State, WindowState};
 use std::env;
 use std::env::args;
 use std::error::Error;
 use std::io::prelude::*;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use logs::{LogContext, LogFilter, LogOptions, ShellLogs};
 use traits::app::{AppEvent, AppCommand, AppMethods};
 use traits::view::*;
 use traits::window::{WindowEvent, WindowCommand};
@@ -63,25 +91,159 @@ const PKG_NAME: &'static str = env!("CARGO_PKG_NAME");
 
 fn main() {
 
-    let logs = logs::Logger::init();
+    let options = match cli::parse(args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\nRun `{} --help` to list the options.", err, PKG_NAME);
+            std::process::exit(1);
+        }
+    };
+
+    if options.help {
+        print!("{}", cli::usage(PKG_NAME));
+        std::process::exit(0);
+    }
+
+    if options.version {
+        println!("{} {}", PKG_NAME, PKG_VERSION);
+        std::process::exit(0);
+    }
+
+    if let Some(ref command) = options.profile_command {
+        let result = match *command {
+            cli::ProfileCommand::List => {
+                profile::list().map(|names| for name in names {
+                    println!("{}", name);
+                })
+            }
+            cli::ProfileCommand::Create(ref name) => profile::create(name),
+            cli::ProfileCommand::Delete(ref name) => profile::delete(name),
+        };
+        match result {
+            Ok(()) => std::process::exit(0),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let log_filter = options
+        .log_filter
+        .clone()
+        .or_else(|| env::var(logs::LOG_ENV).ok())
+        .map_or(Ok(LogFilter::new(options.log_level)),
+                |spec| LogFilter::parse(&spec, options.log_level));
+    // Logs hold URLs. Private windows keep them in memory only.
+    let (log_file, log_json) = if options.private {
+        if options.log_file.is_some() || options.log_json.is_some() {
+            eprintln!("Private window: ignoring --log-file and --log-json");
+        }
+        (None, None)
+    } else {
+        (options.log_file.clone(), options.log_json.clone())
+    };
+    let log_options = log_filter.map(|filter| {
+                                         LogOptions {
+                                             filter,
+                                             capacity: options.log_capacity,
+                                             file: log_file,
+                                             json: log_json,
+                                         }
+                                     });
+    let logs = log_options
+        .and_then(logs::Logger::init)
+        .unwrap_or_else(|err| {
+                            eprintln!("{}", err);
+                            std::process::exit(1);
+                        });
 
     info!("starting");
 
-    args()
-        .find(|arg| arg == "--version")
-        .map(|_| {
-                 println!("{} {}", PKG_NAME, PKG_VERSION);
-                 std::process::exit(0);
-             });
+    // Reftests need the same rendering everywhere. Always headless.
+    if options.headless || options.reftest.is_some() {
+        run::<platform::headless::App>(logs, options);
+    } else {
+        run::<App>(logs, options);
+    }
+}
+
+fn run<A: AppMethods>(logs: Arc<ShellLogs>, options: cli::Options) {
 
-    let resources_path = App::get_resources_path().expect("Can't find resources path");
+    let resources_path = options
+        .resources_path
+        .clone()
+        .or_else(A::get_resources_path)
+        .expect("Can't find resources path");
+
+    let profile_dir = match (options.profile_dir.clone(), options.profile.as_ref()) {
+        (Some(dir), _) => Ok(dir),
+        (None, Some(name)) => profile::named(name),
+        (None, None) => profile::named(profile::DEFAULT_PROFILE),
+    };
+    // Private windows read the profile, but don't create, lock or write it.
+    // Servo's data goes elsewhere.
+    let profile = profile_dir
+        .and_then(|dir| if options.private {
+                      Ok(Profile::read_only(dir))
+                  } else {
+                      Profile::open(dir)
+                  })
+        .unwrap_or_else(|err| {
+                            eprintln!("{}", err);
+                            std::process::exit(1);
+                        });
+    info!("Using profile {}", profile.dir().display());
+
+    let private_storage = if options.private {
+        Some(PrivateStorage::new().unwrap_or_else(|err| {
+                                                       eprintln!("{}", err);
+                                                       std::process::exit(1);
+                                                   }))
+    } else {
+        None
+    };
 
     let mut app_state = State::new(AppState::new());
     app_state.get_mut().current_window_index = Some(0);
+    if let Some(history) = profile.load(profile::HISTORY_FILE) {
+        app_state.get_mut().history = history;
+    }
+    if let Some(bookmarks) = profile.load(profile::BOOKMARKS_FILE) {
+        app_state.get_mut().bookmarks = bookmarks;
+    }
+    if let Some(exceptions) = profile.load(profile::BLOCKING_EXCEPTIONS_FILE) {
+        app_state.get_mut().blocking_exceptions = exceptions;
+    }
 
     let mut win_state = State::new(WindowState::new());
+    win_state.get_mut().private = options.private;
+    if let Some(debug_options) = profile.load(profile::DEBUG_OPTIONS_FILE) {
+        win_state.get_mut().debug_options = debug_options;
+    }
+
+    let config_path = profile.path(profile::CONFIG_FILE);
+    let config = config::load(&config_path).unwrap_or_else(|err| {
+                                                   warn!("{}", err);
+                                                   Config::default()
+                                               });
+    win_state.get_mut().tab_title = config.tab_title.clone();
+    app_state.get_mut().config = config;
+    app_state.get_mut().kiosk = options.kiosk;
 
-    let app = App::new(app_state.get()).expect("Can't create application");
+    // The command line wins over the config file. Reftests always render
+    // at the same size.
+    let size = if options.reftest.is_some() {
+        reftest::WINDOW_SIZE
+    } else {
+        options.window_size.unwrap_or_else(|| {
+            let size = app_state.get().config.window_size;
+            (size.width, size.height)
+        })
+    };
+    win_state.get_mut().inner_size = size;
+
+    let app = A::new(app_state.get()).expect("Can't create application");
     let win = app.new_window(win_state.get())
         .expect("Can't create application");
     app_state.snapshot();
@@ -89,31 +251,86 @@ fn main() {
 
     let view = win.new_view().unwrap();
 
-    Servo::configure(resources_path.clone());
+    // Cocoa windows come from the xib, at its size. Both sizes are in
+    // logical pixels. Kiosk windows are fullscreen.
+    if view.get_geometry().view_size != size && !options.kiosk {
+        view.set_inner_size(size.0, size.1);
+    }
+
+    Servo::configure(resources_path.clone(),
+                     &win_state.get().debug_options,
+                     &options,
+                     Some(private_storage
+                              .as_ref()
+                              .map_or(profile.servo_dir(), |s| s.dir().to_owned())));
 
     let servo = {
         let geometry = view.get_geometry();
         let waker = win.new_event_loop_waker();
-        Servo::new(geometry, view.clone(), waker)
+        Servo::new(geometry, view.clone(), waker, clipboard::new_clipboard())
     };
+    logs.set_waker(win.new_event_loop_waker().clone());
+    servo.apply_webrender_debug_options(&win_state.get().debug_options);
+    servo.set_convert_mouse_to_touch(win_state.get().debug_options.convert_mouse_to_touch);
+
+    match Blocker::load(&profile.path(profile::BLOCKLISTS_DIR)) {
+        Ok(blocker) => servo.set_blocker(blocker),
+        Err(err) => warn!("Content blocking disabled: {}", err),
+    }
+    servo.set_blocking_exceptions(&app_state.get().blocking_exceptions);
 
-    let home_url = resources_path
-        .parent()
-        .unwrap()
-        .join("shell_resources")
-        .join("home.html");
-    let home_url = ServoUrl::from_file_path(&home_url)
-        .unwrap()
-        .into_string();
-
-    // Skip first argument (executable), and find the first
-    // argument that doesn't start with `-`
-    let url = args()
-        .skip(1)
-        .find(|arg| !arg.starts_with("-"))
-        .unwrap_or(home_url);
-
-    let browser = servo.new_browser(&url);
+    if options.kiosk {
+        servo.set_kiosk(Some(app_state.get().config.kiosk.clone()));
+    }
+
+    let shell_pages = ShellPages::new().unwrap_or_else(|err| {
+                                               eprintln!("{}", err);
+                                               std::process::exit(1);
+                                           });
+    // Kiosks never show shell pages
+    let home_url = if options.kiosk {
+        "about:blank".to_owned()
+    } else {
+        Route::Home.url()
+    };
+
+    // --screenshot out.png URL: save the page once loaded, and exit
+    let screenshot_path = options.screenshot.clone();
+
+    // --reftest manifest: run the reftests, and exit
+    let mut reftest_runner = options.reftest.as_ref().map(|manifest| {
+        let output_dir = options
+            .reftest_output
+            .clone()
+            .unwrap_or(PathBuf::from(reftest::DEFAULT_OUTPUT_DIR));
+        reftest::Runner::new(manifest, output_dir)
+            .unwrap_or_else(|err| {
+                                eprintln!("{}", err);
+                                std::process::exit(1);
+                            })
+    });
+
+    // Automated runs leave the history and session of the profile alone.
+    // Private windows don't write anything.
+    let persistent = !options.private && screenshot_path.is_none() &&
+                     reftest_runner.is_none();
+
+    // Without URLs, restore the tabs of the previous session
+    let session: Vec<String> = if persistent {
+        profile.load(profile::SESSION_FILE).unwrap_or(vec![])
+    } else {
+        vec![]
+    };
+
+    let urls = match reftest_runner {
+        Some(ref runner) => vec![runner.first_url()],
+        None if !options.urls.is_empty() => options.urls.clone(),
+        None if !session.is_empty() => session,
+        None => vec![app_state.get().config.home_url.clone().unwrap_or(home_url.clone())],
+    };
+
+    let browser = new_browser(&servo, &urls[0]);
+    let browser_id = browser.id;
     servo.select_browser(browser.id);
 
     win_state
@@ -121,11 +338,54 @@ fn main() {
         .tabs
         .append_new(browser)
         .expect("Can't append browser");
+
+    // One tab per URL, the first one stays selected
+    for url in &urls[1..] {
+        open_in_background_tab(&servo, &view, &mut win_state, url)
+            .expect("Can't append browser");
+    }
+
     win.render(win_state.diff(), win_state.get());
     win_state.snapshot();
 
     info!("Servo version: {}", servo.version());
 
+    // --control-socket path: accept automation clients
+    let control = options.control_socket.as_ref().and_then(|path| {
+        ControlServer::new(path, win.new_event_loop_waker().clone())
+            .map_err(|err| warn!("{}", err))
+            .ok()
+    });
+
+    // --screenshot: give up if the page never finishes loading
+    let screenshot_deadline = if screenshot_path.is_some() {
+        let timeout = Duration::from_secs(options.screenshot_timeout);
+        let deadline = Deadline::new(timeout, win.new_event_loop_waker().clone())
+            .unwrap_or_else(|err| {
+                                eprintln!("{}", err);
+                                std::process::exit(1);
+                            });
+        Some(deadline)
+    } else {
+        None
+    };
+
+    // A config change would resize the reftests
+    let config_watcher = if options.reftest.is_none() {
+        ConfigWatcher::new(config_path.clone(), win.new_event_loop_waker().clone())
+    } else {
+        None
+    };
+
+    // Kiosk mode: back to the home page when nobody uses the window
+    let idle_timeout = app_state.get().config.kiosk.idle_timeout;
+    let idle_watcher = if options.kiosk && idle_timeout > 0 {
+        IdleWatcher::new(Duration::from_secs(idle_timeout),
+                         win.new_event_loop_waker().clone())
+    } else {
+        None
+    };
+
     let handle_events = || {
 
         // Loop until no events are available anymore.
@@ -135,16 +395,53 @@ fn main() {
             let win_events = win.get_events();
             let view_events = view.get_events();
             let servo_events = servo.get_events();
+            let control_requests = control.as_ref().map_or(vec![], |c| c.get_requests());
+            let config_changed = config_watcher.as_ref().map_or(false, |w| w.has_changed());
+            let idle = idle_watcher.as_ref().map_or(false, |w| w.has_expired());
+
+            if screenshot_deadline.as_ref().map_or(false, |d| d.has_passed()) {
+                eprintln!("Screenshot: the page didn't load within {} seconds",
+                          options.screenshot_timeout);
+                std::process::exit(1);
+            }
 
             if app_events.is_empty() && win_events.is_empty() && view_events.is_empty() &&
-               servo_events.is_empty() {
+               servo_events.is_empty() && control_requests.is_empty() && !config_changed &&
+               !idle {
                 break;
             }
 
+            if config_changed {
+                reload_config(&config_path, &servo, &view, &mut win_state, &mut app_state);
+            }
+
+            if let Some(ref watcher) = idle_watcher {
+                if !view_events.is_empty() {
+                    watcher.touch();
+                }
+            }
+
+            if idle {
+                info!("Kiosk: idle, going back to the home page");
+                let url = app_state.get().config.home_url.clone().unwrap_or(home_url.clone());
+                handle_win_command(&servo,
+                                   &view,
+                                   &mut win_state,
+                                   &mut app_state,
+                                   WindowCommand::Load(url))
+                        .expect("handle_win_command exception");
+            }
+
             // FIXME: it's really annoying we need this
             let mut force_sync = false;
 
             for event in win_events {
+                if let WindowEvent::WillClose = event {
+                    // Last private window
+                    if let Some(ref storage) = private_storage {
+                        storage.wipe();
+                    }
+                }
                 if handle_win_event(&servo, &view, &mut win_state, &mut app_state, event)
                            .expect("handle_win_event exception") {
                         force_sync = true;
@@ -164,14 +461,84 @@ fn main() {
                         .expect("handle_view_event exception");
             }
 
+            if let Some(ref control) = control {
+                for (client, request) in control_requests {
+                    let result = handle_control_request(&servo,
+                                                        &view,
+                                                        &mut win_state,
+                                                        &mut app_state,
+                                                        request);
+                    control.respond(client, result);
+                }
+            }
+
             for event in servo_events {
+                // Logs made while handling the event are about its tab
+                logs::set_context(event.browser_id().map(|id| log_context(&win_state, id)));
+                let event = show_shell_urls(&shell_pages, event);
+                if let ServoEvent::ShellUrl(id, ref url, from_page) = event {
+                    let route = handle_shell_request(&servo,
+                                                     &view,
+                                                     &mut win_state,
+                                                     &mut app_state,
+                                                     &shell_pages,
+                                                     &logs,
+                                                     id,
+                                                     url,
+                                                     from_page)
+                            .expect("handle_shell_request exception");
+                    if let Some(route) = route {
+                        let recent_logs = logs.peek();
+                        let context = PageContext {
+                            app_state: app_state.get(),
+                            win_state: win_state.get(),
+                            logs: &recent_logs,
+                            config_path: &config_path,
+                            shell_version: env!("CARGO_PKG_VERSION"),
+                            servo_version: servo.version(),
+                            resources_path: &resources_path,
+                            backend: A::get_name(),
+                            geometry: view.get_geometry(),
+                            prefs: servo.modified_prefs(),
+                        };
+                        match shell_pages.open(route, &context) {
+                            Ok(page_url) => servo.load_url(id, page_url),
+                            Err(err) => warn!("{}", err),
+                        }
+                    }
+                }
+                if let Some(ref control) = control {
+                    control.notify(&event);
+                }
+                let mut exit_code = None;
+                if let Some(ref mut runner) = reftest_runner {
+                    exit_code = runner.handle_event(&servo, browser_id, &event);
+                }
+                if let Some(ref path) = screenshot_path {
+                    match event {
+                        ServoEvent::LoadEnd(..) => servo.screenshot(path.clone()),
+                        ServoEvent::ScreenshotTaken(_, ref result) => {
+                            exit_code = Some(if result.is_ok() { 0 } else { 1 });
+                        }
+                        _ => {}
+                    }
+                }
                 handle_servo_event(&servo, &view, &mut win_state, &mut app_state, event)
                         .expect("handle_servo_event exception");
+                if let Some(code) = exit_code {
+                    std::process::exit(code);
+                }
             }
+            logs::set_context(None);
 
             if app_state.has_changed() || win_state.has_changed() {
-                app.render(app_state.diff(), app_state.get());
-                win.render(win_state.diff(), win_state.get());
+                let app_diff = app_state.diff();
+                let win_diff = win_state.diff();
+                if persistent {
+                    persist(&profile, &app_diff, &win_diff, app_state.get(), win_state.get());
+                }
+                app.render(app_diff, app_state.get());
+                win.render(win_diff, win_state.get());
                 app_state.snapshot();
                 win_state.snapshot();
             }
@@ -182,10 +549,23 @@ fn main() {
         // Here, only stuff that we know for sure won't trigger any
         // new events
 
-        // FIXME: logs will grow until pulled
+        // servoshell://logs polls the logs
+        let logs_url = Route::Logs.url();
+        let shows_logs = win_state
+            .get()
+            .tabs
+            .alive_browsers()
+            .iter()
+            .any(|b| b.url.as_ref() == Some(&logs_url));
+        // Logs made by other threads wake us up while they are shown
+        logs.watch(shows_logs || win_state.get().logs_visible);
+
         if win_state.get().logs_visible {
             win.append_logs(&logs.get_logs());
         }
+        if shows_logs {
+            shell_pages.update_logs(&logs);
+        }
     };
 
     view.set_live_resize_callback(&handle_events);
@@ -197,7 +577,7 @@ fn main() {
 fn handle_win_event(servo: &Servo,
                     view: &Rc<ViewMethods>,
                     win_state: &mut State<WindowState>,
-                    _app_state: &mut State<AppState>,
+                    app_state: &mut State<AppState>,
                     event: WindowEvent)
                     -> Result<bool, &'static str> {
 
@@ -228,160 +608,674 @@ fn handle_win_event(servo: &Servo,
                 .mut_fg_browser()?
                 .urlbar_focused = focused;
         }
-        WindowEvent::DoCommand(cmd) => {
-            let bid = win_state.get().tabs.ref_fg_browser()?.id;
+        WindowEvent::CommandPaletteQueryChanged(query) => {
+            if win_state.get().command_palette.is_some() {
+                let matches = palette::search(&query, app_state.get(), win_state.get());
+                win_state.get_mut().command_palette = Some(CommandPaletteState { query, matches });
+            }
+        }
+        WindowEvent::CommandPaletteSubmit(idx) => {
+            let cmd = win_state
+                .get()
+                .command_palette
+                .as_ref()
+                .and_then(|palette| palette.matches.get(idx))
+                .map(|m| m.command.clone());
+            win_state.get_mut().command_palette = None;
             match cmd {
-                WindowCommand::Stop => {
-                    // FIXME
+                Some(cmd) => handle_win_command(servo, view, win_state, app_state, cmd)?,
+                None => warn!("No command palette entry at index {}", idx),
+            }
+        }
+        WindowEvent::CommandPaletteClosed => {
+            win_state.get_mut().command_palette = None;
+        }
+        WindowEvent::ContextMenuItemSelected(item) => {
+            let link = win_state
+                .get()
+                .context_menu
+                .as_ref()
+                .and_then(|menu| menu.link.clone());
+            win_state.get_mut().context_menu = None;
+            if app_state.get().kiosk {
+                info!("Kiosk: ignoring {:?}", item);
+                return Ok(false);
+            }
+            match (item, link) {
+                (ContextMenuItem::OpenLinkInNewTab, Some(link)) => {
+                    open_in_background_tab(servo, view, win_state, &link)?;
                 }
-                WindowCommand::Reload => {
-                    servo.reload(bid);
+                (ContextMenuItem::CopyLinkAddress, Some(link)) => {
+                    if let Err(err) = servo.clipboard().set_contents(link) {
+                        warn!("Can't copy link address: {}", err);
+                    }
                 }
-                WindowCommand::NavigateBack => {
-                    servo.go_back(bid);
+                (ContextMenuItem::OpenLinkInDefaultBrowser, Some(link)) => {
+                    open::that(link).ok();
                 }
-                WindowCommand::NavigateForward => {
-                    servo.go_forward(bid);
+                (ContextMenuItem::NavigateBack, _) => {
+                    handle_win_command(servo,
+                                       view,
+                                       win_state,
+                                       app_state,
+                                       WindowCommand::NavigateBack)?;
                 }
-                WindowCommand::OpenLocation => {
-                    win_state
-                        .get_mut()
-                        .tabs
-                        .mut_fg_browser()?
-                        .urlbar_focused = true;
+                (ContextMenuItem::NavigateForward, _) => {
+                    handle_win_command(servo,
+                                       view,
+                                       win_state,
+                                       app_state,
+                                       WindowCommand::NavigateForward)?;
                 }
-                WindowCommand::OpenInDefaultBrowser => {
-                    if let Some(ref url) = win_state.get().tabs.ref_fg_browser()?.url {
-                        open::that(url.clone()).ok();
+                (ContextMenuItem::Reload, _) => {
+                    handle_win_command(servo, view, win_state, app_state, WindowCommand::Reload)?;
+                }
+                (ContextMenuItem::ViewPageSource, _) => {
+                    let url = win_state.get().tabs.ref_fg_browser()?.url.clone();
+                    if let Some(url) = url {
+                        let source = format!("view-source:{}", url);
+                        handle_win_command(servo,
+                                           view,
+                                           win_state,
+                                           app_state,
+                                           WindowCommand::NewTab)?;
+                        handle_win_command(servo,
+                                           view,
+                                           win_state,
+                                           app_state,
+                                           WindowCommand::Load(source))?;
                     }
                 }
-                WindowCommand::ZoomIn => {
-                    win_state.get_mut().tabs.mut_fg_browser()?.zoom *= 1.1;
-                    servo.zoom(win_state.get().tabs.ref_fg_browser()?.zoom);
+                (item, None) => warn!("Context menu item {:?} requires a link", item),
+            }
+        }
+        WindowEvent::ContextMenuClosed => {
+            win_state.get_mut().context_menu = None;
+        }
+        // Shortcuts, menus, and the back and forward mouse buttons
+        WindowEvent::DoCommand(cmd) => {
+            if app_state.get().kiosk {
+                info!("Kiosk: ignoring {}", cmd.name());
+            } else {
+                handle_win_command(servo, view, win_state, app_state, cmd)?;
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Generated pages are loaded from files, show their servoshell:// URL
+/// instead.
+fn show_shell_urls(shell_pages: &ShellPages, event: ServoEvent) -> ServoEvent {
+    match event {
+        ServoEvent::HistoryChanged(id, mut entries, current) => {
+            for entry in &mut entries {
+                if let Some(url) = shell_pages.shell_url(&entry.url) {
+                    entry.url = url;
+                }
+            }
+            ServoEvent::HistoryChanged(id, entries, current)
+        }
+        event => event,
+    }
+}
+
+/// servoshell:// URLs can only be opened once the browser exists.
+/// A tab for `url`, or an empty one if it isn't a URL.
+fn new_browser(servo: &Servo, url: &str) -> BrowserState {
+    let blank = || ServoUrl::parse("about:blank").expect("about:blank is a URL");
+    match ServoUrl::parse(url) {
+        Ok(ref url) if url.scheme() == shell_pages::SCHEME => {
+            let browser = servo.new_browser(blank());
+            servo.open_shell_url(browser.id, url.clone());
+            browser
+        }
+        Ok(url) => servo.new_browser(url),
+        Err(err) => {
+            warn!("Can't parse url `{}`: {}", url, err);
+            servo.new_browser(blank())
+        }
+    }
+}
+
+/// The page to show in the browser, if any. Commands are only accepted
+/// from the shell page offering them, while it's shown. It's shown again
+/// once the command changed something.
+fn handle_shell_request(servo: &Servo,
+                        view: &Rc<ViewMethods>,
+                        win_state: &mut State<WindowState>,
+                        app_state: &mut State<AppState>,
+                        shell_pages: &ShellPages,
+                        logs: &ShellLogs,
+                        id: BrowserId,
+                        url: &ServoUrl,
+                        from_page: bool)
+                        -> Result<Option<Route>, &'static str> {
+    if app_state.get().kiosk {
+        info!("Kiosk: {} denied", url);
+        return Ok(None);
+    }
+    let request = match shell_pages::parse(url) {
+        Ok(request) => request,
+        Err(err) => {
+            warn!("{}", err);
+            return Ok(None);
+        }
+    };
+    let command = match request {
+        ShellRequest::Page(route) => return Ok(Some(route)),
+        ShellRequest::External(url) => {
+            open::that(url).ok();
+            return Ok(None);
+        }
+        ShellRequest::Command(command) => command,
+    };
+    let current_route = win_state
+        .get()
+        .tabs
+        .alive_browsers()
+        .into_iter()
+        .find(|b| b.id == id)
+        .and_then(|b| b.url.as_ref())
+        .and_then(|url| ServoUrl::parse(url).ok())
+        .and_then(|url| match shell_pages::parse(&url) {
+                      Ok(ShellRequest::Page(route)) => Some(route),
+                      _ => None,
+                  });
+    let route = match current_route {
+        Some(route) if from_page && route == command.route() => route,
+        _ => {
+            warn!("Ignoring {:?}: not sent by {}", command, command.route().url());
+            return Ok(None);
+        }
+    };
+    match command {
+        PageCommand::ClearHistory => {
+            handle_app_event(servo,
+                             view,
+                             win_state,
+                             app_state,
+                             AppEvent::DoCommand(AppCommand::ClearHistory))?;
+        }
+        PageCommand::ToggleDarkTheme => {
+            handle_app_event(servo,
+                             view,
+                             win_state,
+                             app_state,
+                             AppEvent::DoCommand(AppCommand::ToggleOptionDarkTheme))?;
+        }
+        PageCommand::RemoveBookmark(url) => {
+            app_state.get_mut().bookmarks.retain(|b| b.url != url);
+        }
+        PageCommand::CopyDiagnostics => {
+            if let Err(err) = servo.clipboard().set_contents(shell_pages.diagnostics()) {
+                warn!("Can't copy diagnostics: {}", err);
+            }
+            // Nothing changed, the page stays as it is
+            return Ok(None);
+        }
+        PageCommand::CopyLogs => {
+            let text: Vec<String> = logs.peek()
+                .iter()
+                .map(|l| format!("{} - {}: {}", l.level, l.target, l.message))
+                .collect();
+            if let Err(err) = servo.clipboard().set_contents(text.join("\n")) {
+                warn!("Can't copy logs: {}", err);
+            }
+            // Keep the filters of the page
+            return Ok(None);
+        }
+    }
+    Ok(Some(route))
+}
+
+fn open_in_background_tab(servo: &Servo,
+                          view: &Rc<ViewMethods>,
+                          win_state: &mut State<WindowState>,
+                          url: &str)
+                          -> Result<(), &'static str> {
+    if ServoUrl::parse(url).is_err() {
+        warn!("Can't parse url: {}", url);
+        return Ok(());
+    }
+    let fg = win_state.get().tabs.ref_fg_browser()?.id;
+    // new_browser selects the new browser
+    let browser = new_browser(servo, url);
+    win_state.get_mut().tabs.append_new(browser)?;
+    servo.select_browser(fg);
+    servo.update_geometry(view.get_geometry());
+    Ok(())
+}
+
+fn open_context_menu(servo: &Servo,
+                     win_state: &mut State<WindowState>,
+                     x: i32,
+                     y: i32)
+                     -> Result<(), &'static str> {
+    let fg = win_state.get().tabs.ref_fg_browser()?.id;
+    let link = servo.hovered_link(fg).map(|url| url.into_string());
+    let menu = ContextMenuState::new(x, y, link, win_state.get().tabs.ref_fg_browser()?);
+    win_state.get_mut().context_menu = Some(menu);
+    Ok(())
+}
+
+fn log_context(win_state: &State<WindowState>, id: BrowserId) -> LogContext {
+    LogContext {
+        browser: id,
+        url: win_state
+            .get()
+            .tabs
+            .alive_browsers()
+            .into_iter()
+            .find(|b| b.id == id)
+            .and_then(|b| b.url.clone()),
+    }
+}
+
+/// Logs made while running the command are about the foreground tab.
+fn handle_win_command(servo: &Servo,
+                      view: &Rc<ViewMethods>,
+                      win_state: &mut State<WindowState>,
+                      app_state: &mut State<AppState>,
+                      cmd: WindowCommand)
+                      -> Result<(), &'static str> {
+    let bid = win_state.get().tabs.ref_fg_browser()?.id;
+    let previous = logs::set_context(Some(log_context(win_state, bid)));
+    let result = run_win_command(servo, view, win_state, app_state, bid, cmd);
+    logs::set_context(previous);
+    result
+}
+
+fn run_win_command(servo: &Servo,
+                   view: &Rc<ViewMethods>,
+                   win_state: &mut State<WindowState>,
+                   app_state: &mut State<AppState>,
+                   bid: BrowserId,
+                   cmd: WindowCommand)
+                   -> Result<(), &'static str> {
+    match cmd {
+        WindowCommand::Stop => {
+            // FIXME
+        }
+        WindowCommand::Reload => {
+            servo.reload(bid);
+        }
+        WindowCommand::NavigateBack => {
+            servo.go_back(bid);
+        }
+        WindowCommand::NavigateForward => {
+            servo.go_forward(bid);
+        }
+        WindowCommand::OpenLocation => {
+            win_state
+                .get_mut()
+                .tabs
+                .mut_fg_browser()?
+                .urlbar_focused = true;
+        }
+        WindowCommand::OpenInDefaultBrowser => {
+            if let Some(ref url) = win_state.get().tabs.ref_fg_browser()?.url {
+                open::that(url.clone()).ok();
+            }
+        }
+        WindowCommand::ZoomIn => {
+            win_state.get_mut().tabs.mut_fg_browser()?.zoom *= 1.1;
+            servo.zoom(win_state.get().tabs.ref_fg_browser()?.zoom);
+        }
+        WindowCommand::ZoomOut => {
+            win_state.get_mut().tabs.mut_fg_browser()?.zoom /= 1.1;
+            servo.zoom(win_state.get().tabs.ref_fg_browser()?.zoom);
+        }
+        WindowCommand::ZoomToActualSize => {
+            win_state.get_mut().tabs.mut_fg_browser()?.zoom = 1.0;
+            servo.reset_zoom();
+        }
+
+        WindowCommand::ToggleSidebar => {
+            win_state.get_mut().sidebar_is_open = !win_state.get().sidebar_is_open;
+        }
+
+        WindowCommand::ShowOptions => {
+            win_state.get_mut().options_open = !win_state.get().options_open;
+        }
+
+        WindowCommand::Load(request) => {
+            win_state.get_mut().tabs.mut_fg_browser()?.user_input = Some(request.clone());
+            win_state
+                .get_mut()
+                .tabs
+                .mut_fg_browser()?
+                .urlbar_focused = false;
+            let url = ServoUrl::parse(&request)
+                .or_else(|error| {
+                    // See: https://github.com/paulrouget/servoshell/issues/59
+                    if request.ends_with(".com") || request.ends_with(".org") ||
+                       request.ends_with(".net") {
+                        ServoUrl::parse(&format!("http://{}", request))
+                    } else {
+                        Err(error)
+                    }
+                })
+                .or_else(|_| {
+                             ServoUrl::parse(&app_state.get().config.search(&request))
+                         });
+            match url {
+                Ok(ref url) if url.scheme() == shell_pages::SCHEME => {
+                    // Typed URLs don't go through allow_navigation
+                    servo.open_shell_url(bid, url.clone());
                 }
-                WindowCommand::ZoomOut => {
-                    win_state.get_mut().tabs.mut_fg_browser()?.zoom /= 1.1;
-                    servo.zoom(win_state.get().tabs.ref_fg_browser()?.zoom);
+                Ok(url) => servo.load_url(bid, url),
+                Err(err) => warn!("Can't parse url: {}", err),
+            }
+        }
+        WindowCommand::ToggleOptionShowLogs => {
+            win_state.get_mut().logs_visible = !win_state.get().logs_visible;
+        }
+        WindowCommand::OpenLogs => {
+            let mut browser = new_browser(servo, &Route::Logs.url());
+            browser.is_background = false;
+            win_state.get_mut().tabs.append_new(browser)?;
+            let new = win_state.get().tabs.ref_fg_browser()?.id;
+            servo.select_browser(new);
+            servo.update_geometry(view.get_geometry());
+        }
+        WindowCommand::NewTab => {
+            let mut browser = new_browser(servo, "about:blank");
+            browser.is_background = false;
+            if cfg!(all(not(feature = "force-glutin"), target_os = "macos")) {
+                browser.urlbar_focused = true;
+            }
+            win_state.get_mut().tabs.append_new(browser)?;
+            let new = win_state.get().tabs.ref_fg_browser()?.id;
+            servo.select_browser(new);
+            servo.update_geometry(view.get_geometry());
+        }
+        WindowCommand::CloseTab => {
+            if win_state.get().tabs.has_more_than_one() {
+                let old = win_state.get_mut().tabs.kill_fg()?;
+                servo.close_browser(old);
+                let new = win_state.get().tabs.ref_fg_browser()?.id;
+                servo.select_browser(new);
+            }
+        }
+        WindowCommand::PrevTab => {
+            if win_state.get().tabs.has_more_than_one() {
+                if win_state.get().tabs.can_select_prev().unwrap() {
+                    win_state.get_mut().tabs.select_prev()?;
+                } else {
+                    win_state.get_mut().tabs.select_last()?;
                 }
-                WindowCommand::ZoomToActualSize => {
-                    win_state.get_mut().tabs.mut_fg_browser()?.zoom = 1.0;
-                    servo.reset_zoom();
+                let new = win_state.get().tabs.ref_fg_browser()?.id;
+                servo.select_browser(new);
+            }
+        }
+        WindowCommand::NextTab => {
+            if win_state.get().tabs.has_more_than_one() {
+                if win_state.get().tabs.can_select_next().unwrap() {
+                    win_state.get_mut().tabs.select_next()?;
+                } else {
+                    win_state.get_mut().tabs.select_first()?;
                 }
+                let new = win_state.get().tabs.ref_fg_browser()?.id;
+                servo.select_browser(new);
+            }
+        }
+        WindowCommand::SelectTab(idx) => {
+            if win_state.get().tabs.can_select_nth(idx) {
+                win_state.get_mut().tabs.select_nth(idx)?;
+                let new = win_state.get().tabs.ref_fg_browser()?.id;
+                servo.select_browser(new);
+            }
+        }
+        WindowCommand::ToggleOptionFragmentBorders => {
+            win_state.get_mut().debug_options.show_fragment_borders =
+                !win_state.get().debug_options.show_fragment_borders;
+            info!("Fragment borders option will apply after a restart");
+        }
 
-                WindowCommand::ToggleSidebar => {
-                    win_state.get_mut().sidebar_is_open = !win_state.get().sidebar_is_open;
-                }
+        WindowCommand::ToggleOptionParallelDisplayListBuidling => {
+            win_state.get_mut().debug_options.parallel_display_list_building =
+                !win_state.get().debug_options.parallel_display_list_building;
+            info!("Parallel display list building option will apply after a restart");
+        }
 
-                WindowCommand::ShowOptions => {
-                    win_state.get_mut().options_open = !win_state.get().options_open;
+        WindowCommand::ToggleOptionShowParallelLayout => {
+            win_state.get_mut().debug_options.show_parallel_layout =
+                !win_state.get().debug_options.show_parallel_layout;
+            info!("Parallel layout option will apply after a restart");
+        }
+
+        WindowCommand::ToggleOptionConvertMouseToTouch => {
+            win_state.get_mut().debug_options.convert_mouse_to_touch =
+                !win_state.get().debug_options.convert_mouse_to_touch;
+            servo.set_convert_mouse_to_touch(win_state.get().debug_options.convert_mouse_to_touch);
+        }
+
+        WindowCommand::ToggleOptionTileBorders => {
+            win_state.get_mut().debug_options.show_tiles_borders =
+                !win_state.get().debug_options.show_tiles_borders;
+            info!("Tile borders option will apply after a restart");
+        }
+
+        WindowCommand::ToggleOptionWRProfiler => {
+            win_state.get_mut().debug_options.wr_profiler =
+                !win_state.get().debug_options.wr_profiler;
+            servo.toggle_webrender_debug_option(WebRenderDebugOption::Profiler);
+        }
+
+        WindowCommand::ToggleOptionWRTextureCacheDebug => {
+            win_state.get_mut().debug_options.wr_texture_cache_debug =
+                !win_state.get().debug_options.wr_texture_cache_debug;
+            servo.toggle_webrender_debug_option(WebRenderDebugOption::TextureCacheDebug);
+        }
+
+        WindowCommand::ToggleOptionWRTargetDebug => {
+            win_state.get_mut().debug_options.wr_render_target_debug =
+                !win_state.get().debug_options.wr_render_target_debug;
+            servo.toggle_webrender_debug_option(WebRenderDebugOption::RenderTargetDebug);
+        }
+
+        WindowCommand::ShowCommandPalette => {
+            let matches = palette::search("", app_state.get(), win_state.get());
+            win_state.get_mut().command_palette = Some(CommandPaletteState {
+                                                           query: String::new(),
+                                                           matches,
+                                                       });
+        }
+
+        WindowCommand::CopyUrl => {
+            if let Some(url) = win_state.get().tabs.ref_fg_browser()?.url.clone() {
+                if let Err(err) = servo.clipboard().set_contents(url) {
+                    warn!("Can't copy url: {}", err);
                 }
+            }
+        }
 
-                WindowCommand::Load(request) => {
-                    win_state.get_mut().tabs.mut_fg_browser()?.user_input = Some(request.clone());
-                    win_state
-                        .get_mut()
-                        .tabs
-                        .mut_fg_browser()?
-                        .urlbar_focused = false;
-                    let url = ServoUrl::parse(&request)
-                        .or_else(|error| {
-                            // See: https://github.com/paulrouget/servoshell/issues/59
-                            if request.ends_with(".com") || request.ends_with(".org") ||
-                               request.ends_with(".net") {
-                                ServoUrl::parse(&format!("http://{}", request))
-                            } else {
-                                Err(error)
-                            }
-                        })
-                        .or_else(|_| {
-                                     ServoUrl::parse(&format!("https://duckduckgo.com/html/?q={}",
-                                                              request))
-                                 });
-                    match url {
-                        Ok(url) => servo.load_url(bid, url),
-                        Err(err) => warn!("Can't parse url: {}", err),
+        WindowCommand::PasteAndGo => {
+            match servo.clipboard().get_contents() {
+                Ok(contents) => {
+                    let request = contents.trim().to_owned();
+                    if !request.is_empty() {
+                        handle_win_command(servo,
+                                           view,
+                                           win_state,
+                                           app_state,
+                                           WindowCommand::Load(request))?;
                     }
                 }
-                WindowCommand::ToggleOptionShowLogs => {
-                    win_state.get_mut().logs_visible = !win_state.get().logs_visible;
-                }
-                WindowCommand::NewTab => {
-                    let mut browser = servo.new_browser("about:blank");
-                    browser.is_background = false;
-                    if cfg!(all(not(feature = "force-glutin"), target_os = "macos")) {
-                        browser.urlbar_focused = true;
-                    }
-                    win_state.get_mut().tabs.append_new(browser)?;
-                    let new = win_state.get().tabs.ref_fg_browser()?.id;
-                    servo.select_browser(new);
-                    servo.update_geometry(view.get_geometry());
-                }
-                WindowCommand::CloseTab => {
-                    if win_state.get().tabs.has_more_than_one() {
-                        let old = win_state.get_mut().tabs.kill_fg()?;
-                        servo.close_browser(old);
-                        let new = win_state.get().tabs.ref_fg_browser()?.id;
-                        servo.select_browser(new);
+                Err(err) => warn!("Can't paste: {}", err),
+            }
+        }
+
+        WindowCommand::Screenshot(path) => {
+            if !path.is_empty() {
+                servo.screenshot(PathBuf::from(path));
+            } else {
+                match env::home_dir() {
+                    Some(home) => {
+                        let secs = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map(|d| d.as_secs())
+                            .unwrap_or(0);
+                        servo.screenshot(home.join(format!("servoshell-{}.png", secs)));
                     }
+                    None => warn!("Impossible to get your home dir!"),
                 }
-                WindowCommand::PrevTab => {
-                    if win_state.get().tabs.has_more_than_one() {
-                        if win_state.get().tabs.can_select_prev().unwrap() {
-                            win_state.get_mut().tabs.select_prev()?;
-                        } else {
-                            win_state.get_mut().tabs.select_last()?;
+            }
+        }
+
+        WindowCommand::ToggleContentBlocking => {
+            let host = win_state
+                .get()
+                .tabs
+                .ref_fg_browser()?
+                .url
+                .as_ref()
+                .and_then(|url| ServoUrl::parse(url).ok())
+                .and_then(|url| url.host_str().map(|host| host.to_lowercase()));
+            match host {
+                Some(host) => {
+                    let exceptions = &mut app_state.get_mut().blocking_exceptions;
+                    match exceptions.iter().position(|site| *site == host) {
+                        Some(idx) => {
+                            exceptions.remove(idx);
+                            info!("Content blocking enabled for {}", host);
                         }
-                        let new = win_state.get().tabs.ref_fg_browser()?.id;
-                        servo.select_browser(new);
-                    }
-                }
-                WindowCommand::NextTab => {
-                    if win_state.get().tabs.has_more_than_one() {
-                        if win_state.get().tabs.can_select_next().unwrap() {
-                            win_state.get_mut().tabs.select_next()?;
-                        } else {
-                            win_state.get_mut().tabs.select_first()?;
+                        None => {
+                            info!("Content blocking disabled for {}", host);
+                            exceptions.push(host);
                         }
-                        let new = win_state.get().tabs.ref_fg_browser()?.id;
-                        servo.select_browser(new);
                     }
+                    servo.set_blocking_exceptions(exceptions);
                 }
-                WindowCommand::SelectTab(idx) => {
-                    if win_state.get().tabs.can_select_nth(idx) {
-                        win_state.get_mut().tabs.select_nth(idx)?;
-                        let new = win_state.get().tabs.ref_fg_browser()?.id;
-                        servo.select_browser(new);
-                    }
-                }
-                WindowCommand::ToggleOptionFragmentBorders => {}
-                WindowCommand::ToggleOptionParallelDisplayListBuidling => {}
-                WindowCommand::ToggleOptionShowParallelLayout => {}
-                WindowCommand::ToggleOptionConvertMouseToTouch => {}
-                WindowCommand::ToggleOptionTileBorders => {}
+                None => warn!("No site to toggle content blocking for"),
+            }
+        }
 
-                WindowCommand::ToggleOptionWRProfiler => {
-                    win_state.get_mut().debug_options.wr_profiler =
-                        !win_state.get().debug_options.wr_profiler;
-                    servo.toggle_webrender_debug_option(WebRenderDebugOption::Profiler);
+        WindowCommand::BookmarkPage => {
+            let (url, title) = {
+                let browser = win_state.get().tabs.ref_fg_browser()?;
+                (browser.url.clone(), browser.title.clone())
+            };
+            if let Some(url) = url {
+                if !app_state.get().bookmarks.iter().any(|b| b.url == url) {
+                    app_state
+                        .get_mut()
+                        .bookmarks
+                        .push(Bookmark { url, title });
                 }
+            }
+        }
+    }
+    Ok(())
+}
 
-                WindowCommand::ToggleOptionWRTextureCacheDebug => {
-                    win_state.get_mut().debug_options.wr_texture_cache_debug =
-                        !win_state.get().debug_options.wr_texture_cache_debug;
-                    servo.toggle_webrender_debug_option(WebRenderDebugOption::TextureCacheDebug);
-                }
+/// Save to the profile what changed and must survive a restart.
+fn persist(profile: &Profile,
+           app_diff: &[ChangeType],
+           win_diff: &[ChangeType],
+           app_state: &AppState,
+           win_state: &WindowState) {
+    use state::DiffKey as K;
+    let changed = |diff: &[ChangeType], key: K| {
+        diff.iter()
+            .any(|change| match *change {
+                     ChangeType::Modified(ref keys) |
+                     ChangeType::Added(ref keys) |
+                     ChangeType::Removed(ref keys) => keys.first() == Some(&key),
+                 })
+    };
+    if changed(app_diff, K::history) {
+        profile.save(profile::HISTORY_FILE, &app_state.history);
+    }
+    if changed(app_diff, K::bookmarks) {
+        profile.save(profile::BOOKMARKS_FILE, &app_state.bookmarks);
+    }
+    if changed(app_diff, K::blocking_exceptions) {
+        profile.save(profile::BLOCKING_EXCEPTIONS_FILE, &app_state.blocking_exceptions);
+    }
+    if changed(win_diff, K::debug_options) {
+        profile.save(profile::DEBUG_OPTIONS_FILE, &win_state.debug_options);
+    }
+    // Tabs opened, closed or navigated
+    let session_changed = win_diff
+        .iter()
+        .any(|change| match *change {
+                 ChangeType::Modified(ref keys) => {
+                     match keys.as_slice() {
+                         &[K::tabs, K::Index(_), K::Alive, K::url] => true,
+                         _ => false,
+                     }
+                 }
+                 ChangeType::Added(ref keys) |
+                 ChangeType::Removed(ref keys) => keys.first() == Some(&K::tabs),
+             });
+    if session_changed {
+        let urls: Vec<&String> = win_state
+            .tabs
+            .alive_browsers()
+            .into_iter()
+            .filter_map(|browser| browser.url.as_ref())
+            .collect();
+        profile.save(profile::SESSION_FILE, &urls);
+    }
+}
 
-                WindowCommand::ToggleOptionWRTargetDebug => {
-                    win_state.get_mut().debug_options.wr_render_target_debug =
-                        !win_state.get().debug_options.wr_render_target_debug;
-                    servo.toggle_webrender_debug_option(WebRenderDebugOption::RenderTargetDebug);
-                }
-            }
+
+fn reload_config(path: &Path,
+                 servo: &Servo,
+                 view: &Rc<ViewMethods>,
+                 win_state: &mut State<WindowState>,
+                 app_state: &mut State<AppState>) {
+    let config = match config::load(path) {
+        Ok(config) => config,
+        Err(err) => {
+            warn!("{}\nKeeping the previous settings.", err);
+            return;
         }
+    };
+    if config == app_state.get().config {
+        return;
     }
-    Ok(false)
+    if config.window_size != app_state.get().config.window_size && !app_state.get().kiosk {
+        view.set_inner_size(config.window_size.width, config.window_size.height);
+    }
+    if app_state.get().kiosk {
+        // FIXME: a new idle timeout needs a restart
+        servo.set_kiosk(Some(config.kiosk.clone()));
+    }
+    win_state.get_mut().tab_title = config.tab_title.clone();
+    app_state.get_mut().config = config;
+    info!("Config reloaded");
 }
 
+fn handle_control_request(servo: &Servo,
+                          view: &Rc<ViewMethods>,
+                          win_state: &mut State<WindowState>,
+                          app_state: &mut State<AppState>,
+                          request: Request)
+                          -> Result<serde_json::Value, String> {
+    match request {
+        Request::Command { command } => {
+            handle_win_command(servo, view, win_state, app_state, command)
+                .map(|()| serde_json::Value::Null)
+                .map_err(|err| err.to_owned())
+        }
+        Request::ViewEvent { event } => {
+            handle_view_event(servo, view, win_state, app_state, event)
+                .map(|()| serde_json::Value::Null)
+                .map_err(|err| err.to_owned())
+        }
+        Request::GetWindowState => {
+            serde_json::to_value(win_state.get()).map_err(|err| err.to_string())
+        }
+        Request::Subscribe { .. } |
+        Request::Unsubscribe { .. } => {
+            // Handled by the server
+            Ok(serde_json::Value::Null)
+        }
+    }
+}
 
 fn handle_app_event(servo: &Servo,
                     view: &Rc<ViewMethods>,
@@ -405,7 +1299,7 @@ fn handle_app_event(servo: &Servo,
         AppEvent::DoCommand(cmd) => {
             match cmd {
                 AppCommand::ClearHistory => {
-                    // FIXME
+                    app_state.get_mut().history.clear();
                 }
                 AppCommand::ToggleOptionDarkTheme => {
                     app_state.get_mut().dark_theme = !app_state.get().dark_theme;
@@ -421,7 +1315,7 @@ fn handle_app_event(servo: &Servo,
 fn handle_view_event(servo: &Servo,
                      view: &Rc<ViewMethods>,
                      win_state: &mut State<WindowState>,
-                     _app_state: &mut State<AppState>,
+                     app_state: &mut State<AppState>,
                      event: ViewEvent)
                      -> Result<(), &'static str> {
 
@@ -431,11 +1325,10 @@ fn handle_view_event(servo: &Servo,
             view.update_drawable();
         }
         ViewEvent::MouseWheel(delta, phase) => {
-            // FIXME: magic value
-            static LINE_HEIGHT: f32 = 38.0;
+            let line_height = app_state.get().config.scroll_line_height;
             let (mut x, mut y) = match delta {
                 MouseScrollDelta::PixelDelta(x, y) => (x, y),
-                MouseScrollDelta::LineDelta(x, y) => (x, y * LINE_HEIGHT),
+                MouseScrollDelta::LineDelta(x, y) => (x, y * line_height),
             };
             if y.abs() >= x.abs() {
                 x = 0.0;
@@ -448,7 +1341,30 @@ fn handle_view_event(servo: &Servo,
             servo.perform_mouse_move(x, y);
         }
         ViewEvent::MouseInput(element_state, button, x, y) => {
+            // Servo gets a touch cancel instead of a tap for long presses
+            let long_press = element_state == ElementState::Released &&
+                             win_state.get().debug_options.convert_mouse_to_touch &&
+                             servo.is_long_press(x, y);
             servo.perform_click(x, y, element_state, button);
+            // No new tabs or context menus in kiosk mode
+            if app_state.get().kiosk {
+                return Ok(());
+            }
+            match (element_state, button) {
+                (ElementState::Pressed, MouseButton::Middle) => {
+                    let fg = win_state.get().tabs.ref_fg_browser()?.id;
+                    if let Some(url) = servo.hovered_link(fg) {
+                        open_in_background_tab(servo, view, win_state, url.as_str())?;
+                    }
+                }
+                (ElementState::Pressed, MouseButton::Right) => {
+                    open_context_menu(servo, win_state, x, y)?;
+                }
+                (ElementState::Released, MouseButton::Left) if long_press => {
+                    open_context_menu(servo, win_state, x, y)?;
+                }
+                _ => {}
+            }
         }
         ViewEvent::KeyEvent(c, key, keystate, modifiers) => {
             let id = win_state
@@ -489,7 +1405,14 @@ fn handle_servo_event(_servo: &Servo,
         ServoEvent::TitleChanged(id, title) => {
             match win_state.get_mut().tabs.find_browser(&id) {
                 Some(browser) => {
-                    browser.title = title;
+                    browser.title = title.clone();
+                    if let Some(ref url) = browser.url {
+                        if let Some(entry) = app_state.get_mut().history.last_mut() {
+                            if entry.url == *url {
+                                entry.title = title;
+                            }
+                        }
+                    }
                 }
                 None => warn!("Got message for unkown browser:  {:?}", id),
             }
@@ -520,6 +1443,20 @@ fn handle_servo_event(_servo: &Servo,
             match win_state.get_mut().tabs.find_browser(&id) {
                 Some(browser) => {
                     let url = entries[current].url.to_string();
+                    let is_new = app_state
+                        .get()
+                        .history
+                        .last()
+                        .map_or(true, |entry| entry.url != url);
+                    if is_new && url != "about:blank" && !shell_pages::is_shell_url(&url) {
+                        app_state
+                            .get_mut()
+                            .history
+                            .push(HistoryEntry {
+                                      url: url.clone(),
+                                      title: browser.title.clone(),
+                                  });
+                    }
                     browser.url = Some(url);
                     browser.can_go_back = current > 0;
                     browser.can_go_forward = current < entries.len() - 1;
@@ -534,14 +1471,23 @@ fn handle_servo_event(_servo: &Servo,
                 app_state.get_mut().cursor = cursor;
             }
         }
+        ServoEvent::NavigationBlocked(id, url) => {
+            info!("Blocked {}", url);
+            match win_state.get_mut().tabs.find_browser(&id) {
+                Some(browser) => browser.blocked_count += 1,
+                None => warn!("Got message for unkown browser:  {:?}", id),
+            }
+        }
         ServoEvent::FaviconChanged(..) => {
-            // FIXME
+            // FIXME: favicons are neither shown nor stored. Only Servo's
+            // HTTP cache keeps them, in the private directory for private
+            // windows. Don't store them in the profile for those.
         }
         ServoEvent::Key(..) => {
             // FIXME
         }
-        ServoEvent::OpenInDefaultBrowser(url) => {
-            open::that(url).ok();
+        ServoEvent::ShellUrl(..) => {
+            // Handled by the event loop, with the shell pages
         }
         ServoEvent::WriteMicrodata(microdata, datatype) => {
             match env::home_dir() {
@@ -590,6 +1536,15 @@ fn handle_servo_event(_servo: &Servo,
                 None => println!("Impossible to get your home dir!"),
             }
         }
+        ServoEvent::FrameCaptured(..) => {
+            // Only used by the reftest runner
+        }
+        ServoEvent::ScreenshotTaken(path, result) => {
+            match result {
+                Ok(()) => info!("Screenshot saved to {}", path.display()),
+                Err(err) => warn!("Can't save screenshot to {}: {}", path.display(), err),
+            }
+        }
     };
     Ok(())
 }
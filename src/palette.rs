@@ -0,0 +1,198 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use state::{AppState, PaletteMatch, PaletteMatchKind, WindowState};
+use traits::window::WindowCommand;
+
+// Don't flood the renderers
+const MAX_MATCHES: usize = 50;
+
+/// Everything the command palette can run: commands, open tabs,
+/// bookmarks and history entries (most recent first).
+fn entries(app_state: &AppState, win_state: &WindowState) -> Vec<PaletteMatch> {
+    let mut entries: Vec<PaletteMatch> = WindowCommand::all()
+        .into_iter()
+        .filter(|cmd| *cmd != WindowCommand::ShowCommandPalette)
+        .map(|cmd| {
+                 PaletteMatch {
+                     kind: PaletteMatchKind::Command,
+                     label: cmd.name().to_owned(),
+                     command: cmd,
+                 }
+             })
+        .collect();
+
+    for (idx, browser) in win_state.tabs.alive_browsers().iter().enumerate() {
+        let title = browser.title.as_ref().map_or("No Title", |t| t.as_str());
+        let url = browser.url.as_ref().map_or("", |u| u.as_str());
+        entries.push(PaletteMatch {
+                         kind: PaletteMatchKind::Tab,
+                         label: format!("{} - {}", title, url),
+                         command: WindowCommand::SelectTab(idx),
+                     });
+    }
+
+    for bookmark in &app_state.bookmarks {
+        let title = bookmark.title.as_ref().map_or("", |t| t.as_str());
+        entries.push(PaletteMatch {
+                         kind: PaletteMatchKind::Bookmark,
+                         label: format!("{} - {}", title, bookmark.url),
+                         command: WindowCommand::Load(bookmark.url.clone()),
+                     });
+    }
+
+    let mut seen: Vec<&str> = Vec::new();
+    for entry in app_state.history.iter().rev() {
+        if seen.contains(&entry.url.as_str()) {
+            continue;
+        }
+        seen.push(&entry.url);
+        let title = entry.title.as_ref().map_or("", |t| t.as_str());
+        entries.push(PaletteMatch {
+                         kind: PaletteMatchKind::History,
+                         label: format!("{} - {}", title, entry.url),
+                         command: WindowCommand::Load(entry.url.clone()),
+                     });
+    }
+
+    entries
+}
+
+/// Entries matching the query, best match first.
+pub fn search(query: &str, app_state: &AppState, win_state: &WindowState) -> Vec<PaletteMatch> {
+    let mut scored: Vec<(i32, PaletteMatch)> = entries(app_state, win_state)
+        .into_iter()
+        .filter_map(|entry| fuzzy_score(query, &entry.label).map(|score| (score, entry)))
+        .collect();
+    // Stable sort: on equal score, commands come before tabs, bookmarks and history
+    scored.sort_by(|a, b| b.0.cmp(&a.0));
+    scored
+        .into_iter()
+        .take(MAX_MATCHES)
+        .map(|(_, entry)| entry)
+        .collect()
+}
+
+fn is_word_boundary(c: char) -> bool {
+    c.is_whitespace() || c == '-' || c == '_' || c == '/' || c == '.' || c == ':'
+}
+
+/// Returns None if the characters of `query` don't all appear, in order,
+/// in `candidate`. Otherwise, the higher the better. Consecutive matches
+/// and matches at the beginning of a word are favored, gaps are penalized.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut query_idx = 0;
+    let mut last_match: Option<usize> = None;
+
+    for (idx, c) in candidate.iter().enumerate() {
+        if query_idx == query.len() {
+            break;
+        }
+        if c.to_lowercase().next() != Some(query[query_idx]) {
+            continue;
+        }
+        score += 1;
+        if idx == 0 || is_word_boundary(candidate[idx - 1]) {
+            score += 8;
+        }
+        match last_match {
+            Some(last) if last + 1 == idx => score += 5,
+            Some(last) => score -= (idx - last - 1) as i32,
+            None => score -= idx as i32,
+        }
+        last_match = Some(idx);
+        query_idx += 1;
+    }
+
+    if query_idx == query.len() {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fuzzy_score, search};
+    use state::{AppState, Bookmark, HistoryEntry, PaletteMatchKind, WindowState};
+    use traits::window::WindowCommand;
+
+    #[test]
+    fn fuzzy_score_needs_every_character_in_order() {
+        assert!(fuzzy_score("nt", "New Tab").is_some());
+        assert!(fuzzy_score("tn", "New Tab").is_none());
+        assert!(fuzzy_score("newtabx", "New Tab").is_none());
+    }
+
+    #[test]
+    fn fuzzy_score_ignores_case_and_spaces() {
+        assert_eq!(fuzzy_score("NEW tab", "New Tab"), fuzzy_score("newtab", "New Tab"));
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("  ", "anything"), Some(0));
+    }
+
+    #[test]
+    fn fuzzy_score_favors_word_starts_and_consecutive_matches() {
+        let word_starts = fuzzy_score("ct", "Close Tab").unwrap();
+        let inside = fuzzy_score("ct", "Select").unwrap();
+        assert!(word_starts > inside);
+        let consecutive = fuzzy_score("zoom", "Zoom In").unwrap();
+        let gaps = fuzzy_score("zoom", "Zero Out Of Memory").unwrap();
+        assert!(consecutive > gaps);
+    }
+
+    #[test]
+    fn search_sorts_by_score_and_keeps_commands_first_on_ties() {
+        let mut app_state = AppState::new();
+        app_state.bookmarks.push(Bookmark {
+                                     url: "https://reload.example".to_owned(),
+                                     title: None,
+                                 });
+        let win_state = WindowState::new();
+        let matches = search("reload", &app_state, &win_state);
+        assert_eq!(matches[0].command, WindowCommand::Reload);
+        assert_eq!(matches[0].kind, PaletteMatchKind::Command);
+        assert!(matches
+                    .iter()
+                    .any(|m| m.kind == PaletteMatchKind::Bookmark));
+        assert!(search("zzzz", &app_state, &win_state).is_empty());
+    }
+
+    #[test]
+    fn search_lists_history_once_most_recent_first() {
+        let mut app_state = AppState::new();
+        for url in &["https://a.example", "https://b.example", "https://a.example"] {
+            app_state.history.push(HistoryEntry {
+                                       url: (*url).to_owned(),
+                                       title: None,
+                                   });
+        }
+        let win_state = WindowState::new();
+        let history: Vec<String> = search("example", &app_state, &win_state)
+            .into_iter()
+            .filter(|m| m.kind == PaletteMatchKind::History)
+            .map(|m| m.label)
+            .collect();
+        assert_eq!(history, vec![" - https://a.example", " - https://b.example"]);
+    }
+
+    #[test]
+    fn search_never_offers_the_palette_itself() {
+        let matches = search("palette", &AppState::new(), &WindowState::new());
+        assert!(!matches
+                     .iter()
+                     .any(|m| m.command == WindowCommand::ShowCommandPalette));
+    }
+}
@@ -208,6 +208,12 @@ impl App {
 impl AppMethods for App {
     fn new<'a>(state: &AppState) -> Result<App, &'a str> {
 
+        if state.kiosk {
+            // FIXME: no fullscreen and exit chord with Cocoa yet. Shell
+            // commands and navigations are still restricted.
+            warn!("Kiosk mode is partial on macOS. Build with --features force-glutin");
+        }
+
         register();
         view::register();
         window::register();
@@ -251,6 +257,10 @@ impl AppMethods for App {
         Ok(app)
     }
 
+    fn get_name() -> &'static str {
+        "cocoa"
+    }
+
     fn get_resources_path() -> Option<PathBuf> {
         Self::get_res_parent().map(|p| p.join("servo_resources"))
     }
@@ -263,10 +273,27 @@ impl AppMethods for App {
                 ChangeType::Modified(keys) => {
                     match keys.as_slice() {
                         &[K::cursor] => self.render_cursor(state.cursor),
-                        _ => println!("App::render: unexpected keys: {:?}", keys),
+                        &[K::history, _..] |
+                        &[K::bookmarks, _..] |
+                        &[K::config, _..] |
+                        &[K::blocking_exceptions, _..] => {
+                            // Nothing to do
+                        }
+                        _ => warn!("App::render: unexpected keys: {:?}", keys),
+                    }
+                }
+                ChangeType::Added(keys) |
+                ChangeType::Removed(keys) => {
+                    match keys.as_slice() {
+                        &[K::history, _..] |
+                        &[K::bookmarks, _..] |
+                        &[K::config, _..] |
+                        &[K::blocking_exceptions, _..] => {
+                            // Nothing to do
+                        }
+                        _ => warn!("App::render: unexpected keys: {:?}", keys),
                     }
                 }
-                _ => println!("App::render: unexpected change type: {:?}", change),
             }
         }
     }
@@ -167,6 +167,14 @@ pub fn register() {
                          store_nsevent as extern "C" fn(&Object, Sel, id));
         class.add_method(sel!(mouseUp:),
                          store_nsevent as extern "C" fn(&Object, Sel, id));
+        class.add_method(sel!(rightMouseDown:),
+                         store_nsevent as extern "C" fn(&Object, Sel, id));
+        class.add_method(sel!(rightMouseUp:),
+                         store_nsevent as extern "C" fn(&Object, Sel, id));
+        class.add_method(sel!(otherMouseDown:),
+                         store_nsevent as extern "C" fn(&Object, Sel, id));
+        class.add_method(sel!(otherMouseUp:),
+                         store_nsevent as extern "C" fn(&Object, Sel, id));
         class.add_method(sel!(mouseMoved:),
                          store_nsevent as extern "C" fn(&Object, Sel, id));
         class.add_method(sel!(keyDown:),
@@ -333,6 +341,21 @@ impl ViewMethods for View {
             msg_send![self.nsview, exitFullScreenModeWithOptions: nil];
         }
     }
+
+    fn set_inner_size(&self, width: u32, height: u32) {
+        unsafe {
+            let nswindow: id = msg_send![self.nsview, window];
+            let content_view: id = msg_send![nswindow, contentView];
+            let view_frame: NSRect = msg_send![self.nsview, frame];
+            let content_frame: NSRect = msg_send![content_view, frame];
+            // Keep the room taken by the toolbar and the tabbar
+            let size = NSSize::new(width as CGFloat + content_frame.size.width -
+                                   view_frame.size.width,
+                                   height as CGFloat + content_frame.size.height -
+                                   view_frame.size.height);
+            msg_send![nswindow, setContentSize: size];
+        }
+    }
 }
 
 
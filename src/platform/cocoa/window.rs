@@ -274,6 +274,18 @@ pub fn register() {
             utils::get_event_queue(this).push(WindowEvent::DoCommand(cmd));
         }
 
+        extern "C" fn context_menu_item_selected(this: &Object, _sel: Sel, item: id) {
+            let idx: NSInteger = unsafe { msg_send![item, tag] };
+            let item = get_win_state()
+                .context_menu
+                .as_ref()
+                .and_then(|menu| menu.items.get(idx as usize));
+            if let Some(item) = item {
+                let event = WindowEvent::ContextMenuItemSelected(item.clone());
+                utils::get_event_queue(this).push(event);
+            }
+        }
+
         extern "C" fn tabview_selected(this: &Object, _sel: Sel, tabview: id, item: id) {
             unsafe {
                 let rendering: bool = *this.get_ivar("rendering");
@@ -363,6 +375,9 @@ pub fn register() {
             class.add_method(sel!(shellSubmitUserInput:),
                              submit_user_input as extern "C" fn(&Object, Sel, id));
 
+            class.add_method(sel!(shellContextMenuItem:),
+                             context_menu_item_selected as extern "C" fn(&Object, Sel, id));
+
             class.add_method(sel!(tabView:didSelectTabViewItem:),
                              tabview_selected as extern "C" fn(&Object, Sel, id, id));
         }
@@ -655,6 +670,42 @@ impl Window {
 
     }
 
+    fn render_context_menu(&self, state: &WindowState) {
+        if let Some(ref menu) = state.context_menu {
+            let nsview = utils::get_view_by_id(self.nswindow, "shellViewServo")
+                .expect("Can't find shellViewServo");
+            unsafe {
+                let delegate: id = msg_send![self.nswindow, delegate];
+                let nsmenu: id = msg_send![class("NSMenu"), alloc];
+                let nsmenu: id = msg_send![nsmenu, initWithTitle:NSString::alloc(nil).init_str("")];
+                msg_send![nsmenu, setAutoenablesItems: NO];
+                for (i, item) in menu.items.iter().enumerate() {
+                    let title = NSString::alloc(nil).init_str(item.label());
+                    let key = NSString::alloc(nil).init_str("");
+                    let nsitem: id = msg_send![class("NSMenuItem"), alloc];
+                    let nsitem: id = msg_send![nsitem,
+                                               initWithTitle:title
+                                               action:sel!(shellContextMenuItem:)
+                                               keyEquivalent:key];
+                    msg_send![nsitem, setTarget: delegate];
+                    msg_send![nsitem, setTag: i as NSInteger];
+                    msg_send![nsmenu, addItem: nsitem];
+                }
+                // Menu coordinates are in device pixels, from the top left corner
+                let frame: NSRect = msg_send![nsview, frame];
+                let hidpi_factor: CGFloat = msg_send![self.nswindow, backingScaleFactor];
+                let x = menu.x as CGFloat / hidpi_factor;
+                let y = frame.size.height - menu.y as CGFloat / hidpi_factor;
+                // Blocks until the menu is closed
+                msg_send![nsmenu,
+                          popUpMenuPositioningItem:nil
+                          atLocation:NSPoint::new(x, y)
+                          inView:nsview];
+            }
+            utils::get_event_queue(unsafe { &*self.nswindow }).push(WindowEvent::ContextMenuClosed);
+        }
+    }
+
     fn render_tab_title(&self, state: &WindowState, index: usize) {
         let title = &state.tabs.find_browser_at(index).unwrap().title;
         let index = state.tabs.index_to_alive_index(index).unwrap();
@@ -780,8 +831,11 @@ impl WindowMethods for Window {
                                 }
                                 K::zoom => self.render_zoom_buttons(current_browser_state),
                                 K::urlbar_focused => self.render_focus(current_browser_state),
+                                K::blocked_count => {
+                                    // Nothing to do
+                                }
                                 _ => {
-                                    println!("Window::render: unexpected Modified keys: {:?}", keys)
+                                    warn!("Window::render: unexpected Modified keys: {:?}", keys)
                                 }
                             }
                         }
@@ -806,14 +860,27 @@ impl WindowMethods for Window {
                         &[K::tabs, K::Index(_), K::user_input] => {
                             // Nothing to do
                         }
+                        &[K::tab_title, _..] => {
+                            // Nothing to do. Real tabs.
+                        }
+                        &[K::command_palette, _..] => {
+                            // FIXME: no command palette UI yet
+                        }
+                        &[K::context_menu] => {
+                            self.render_context_menu(state);
+                        }
+                        &[K::context_menu, _..] => {
+                            // Nothing to do
+                        }
                         &[K::tabs, K::Index(i), K::Alive, K::is_background] |
                         &[K::tabs, K::Index(i), K::Alive, K::can_go_forward] |
                         &[K::tabs, K::Index(i), K::Alive, K::can_go_back] |
                         &[K::tabs, K::Index(i), K::Alive, K::url] |
-                        &[K::tabs, K::Index(i), K::Alive, K::is_loading] if i != idx => {
+                        &[K::tabs, K::Index(i), K::Alive, K::is_loading] |
+                        &[K::tabs, K::Index(i), K::Alive, K::blocked_count] if i != idx => {
                             // Nothing to do
                         }
-                        _ => println!("Window::render: unexpected Modified keys: {:?}", keys),
+                        _ => warn!("Window::render: unexpected Modified keys: {:?}", keys),
                     }
                 }
                 ChangeType::Added(keys) => {
@@ -836,7 +903,13 @@ impl WindowMethods for Window {
                                 self.render_default_webbrowser_button(browser);
                             }
                         }
-                        _ => println!("Window::render: unexpected Added keys: {:?}", keys),
+                        &[K::command_palette, _..] => {
+                            // FIXME: no command palette UI yet
+                        }
+                        &[K::context_menu, _..] => {
+                            // Nothing to do
+                        }
+                        _ => warn!("Window::render: unexpected Added keys: {:?}", keys),
                     }
                 }
                 ChangeType::Removed(keys) => {
@@ -844,7 +917,13 @@ impl WindowMethods for Window {
                         &[K::tabs, K::Index(i), K::Alive] => {
                             self.render_remove_tab(state, i);
                         }
-                        _ => println!("Window::render: unexpected Removed keys: {:?}", keys),
+                        &[K::command_palette, _..] => {
+                            // FIXME: no command palette UI yet
+                        }
+                        &[K::context_menu, _..] => {
+                            // Nothing to do
+                        }
+                        _ => warn!("Window::render: unexpected Removed keys: {:?}", keys),
                     }
                 }
             }
@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use glutin::{self, GlContext};
+use kiosk::Chord;
 use platform::Window;
 use servo::{ServoCursor, EventLoopWaker};
 use state::{AppState, ChangeType, DiffKey, WindowState};
@@ -35,11 +36,14 @@ pub struct App {
     event_loop: RefCell<glutin::EventsLoop>,
     event_loop_waker: Box<EventLoopWaker>,
     windows: Rc<RefCell<HashMap<glutin::WindowId, GlutinWindow>>>,
+    // Kiosk mode. The only shortcut left
+    exit_chord: Option<Chord>,
 }
 
 impl App {
     fn should_exit(&self, event: &glutin::WindowEvent) -> bool {
-        // Exit if window is closed or if Cmd/Ctrl Q
+        // Exit if window is closed or if Cmd/Ctrl Q (the exit chord in
+        // kiosk mode)
         match *event {
             glutin::WindowEvent::Closed => return true,
             _ => {}
@@ -50,13 +54,20 @@ impl App {
                    input: glutin::KeyboardInput {
                        state: glutin::ElementState::Pressed,
                        scancode: _,
-                       virtual_keycode: Some(glutin::VirtualKeyCode::Q),
+                       virtual_keycode: Some(code),
                        modifiers,
                    },
                } = *event {
-            if utils::cmd_or_ctrl(modifiers) {
-                return true;
-            }
+            return match self.exit_chord {
+                       Some(chord) => {
+                           utils::glutin_key_to_script_key(code) == Ok(chord.key) &&
+                           modifiers.ctrl == chord.ctrl &&
+                           modifiers.alt == chord.alt &&
+                           modifiers.shift == chord.shift &&
+                           modifiers.logo == chord.logo
+                       }
+                       None => code == glutin::VirtualKeyCode::Q && utils::cmd_or_ctrl(modifiers),
+                   };
         }
         false
     }
@@ -71,19 +82,30 @@ impl App {
 }
 
 impl AppMethods for App {
-    fn new<'a>(_state: &AppState) -> Result<App, &'a str> {
+    fn new<'a>(state: &AppState) -> Result<App, &'a str> {
 
         let event_loop = glutin::EventsLoop::new();
         let event_loop_waker =
             box WinitEventLoopWaker { proxy: Arc::new(event_loop.create_proxy()) };
         let windows = Rc::new(RefCell::new(HashMap::new()));
+        // The config is validated, the chord parses
+        let exit_chord = if state.kiosk {
+            Chord::parse(&state.config.kiosk.exit_chord).ok()
+        } else {
+            None
+        };
         Ok(App {
                windows,
                event_loop: RefCell::new(event_loop),
                event_loop_waker,
+               exit_chord,
            })
     }
 
+    fn get_name() -> &'static str {
+        "glutin"
+    }
+
     fn get_resources_path() -> Option<PathBuf> {
         // Try current directory. Used for example with "cargo run"
         let p = env::current_dir().unwrap();
@@ -116,10 +138,27 @@ impl AppMethods for App {
                 ChangeType::Modified(keys) => {
                     match keys.as_slice() {
                         &[K::cursor] => self.render_cursor(state.cursor),
-                        _ => println!("App::render: unexpected keys: {:?}", keys),
+                        &[K::history, _..] |
+                        &[K::bookmarks, _..] |
+                        &[K::config, _..] |
+                        &[K::blocking_exceptions, _..] => {
+                            // Nothing to do
+                        }
+                        _ => warn!("App::render: unexpected keys: {:?}", keys),
+                    }
+                }
+                ChangeType::Added(keys) |
+                ChangeType::Removed(keys) => {
+                    match keys.as_slice() {
+                        &[K::history, _..] |
+                        &[K::bookmarks, _..] |
+                        &[K::config, _..] |
+                        &[K::blocking_exceptions, _..] => {
+                            // Nothing to do
+                        }
+                        _ => warn!("App::render: unexpected keys: {:?}", keys),
                     }
                 }
-                _ => println!("App::render: unexpected change type: {:?}", change),
             }
         }
     }
@@ -135,8 +174,13 @@ impl AppMethods for App {
         #[cfg(not(target_os = "windows"))]
         let factor = 1.0;
 
-        let window = glutin::WindowBuilder::new().with_dimensions(1024 * factor as u32,
-                                                                  768 * factor as u32);
+        let (width, height) = state.inner_size;
+        let mut window = glutin::WindowBuilder::new()
+            .with_dimensions((width as f32 * factor) as u32, (height as f32 * factor) as u32);
+        if self.exit_chord.is_some() {
+            let monitor = self.event_loop.borrow().get_primary_monitor();
+            window = window.with_fullscreen(monitor);
+        }
         let context = glutin::ContextBuilder::new()
             .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 2)))
             .with_vsync(true);
@@ -29,6 +29,23 @@ pub struct GlutinWindow {
     window_events: Vec<WindowEvent>,
 }
 
+/// The back and forward buttons. X11 numbers them 8 and 9 (4 to 7 are
+/// the wheel), macOS 3 and 4.
+// FIXME: this version of winit doesn't report them on Windows.
+fn navigation_button(button: glutin::MouseButton) -> Option<WindowCommand> {
+    match button {
+        #[cfg(target_os = "macos")]
+        glutin::MouseButton::Other(3) => Some(WindowCommand::NavigateBack),
+        #[cfg(target_os = "macos")]
+        glutin::MouseButton::Other(4) => Some(WindowCommand::NavigateForward),
+        #[cfg(not(target_os = "macos"))]
+        glutin::MouseButton::Other(8) => Some(WindowCommand::NavigateBack),
+        #[cfg(not(target_os = "macos"))]
+        glutin::MouseButton::Other(9) => Some(WindowCommand::NavigateForward),
+        _ => None,
+    }
+}
+
 impl GlutinWindow {
     pub fn glutin_event_to_command(&self, event: &glutin::WindowEvent) -> Option<WindowCommand> {
         match *event {
@@ -45,6 +62,18 @@ impl GlutinWindow {
                        utils::cmd_or_ctrl(modifiers),
                        modifiers.ctrl,
                        modifiers.shift) {
+                    (Some(glutin::VirtualKeyCode::P), true, _, true) => {
+                        Some(WindowCommand::ShowCommandPalette)
+                    }
+                    (Some(glutin::VirtualKeyCode::D), true, _, _) => {
+                        Some(WindowCommand::BookmarkPage)
+                    }
+                    (Some(glutin::VirtualKeyCode::C), true, _, true) => {
+                        Some(WindowCommand::CopyUrl)
+                    }
+                    (Some(glutin::VirtualKeyCode::V), true, _, true) => {
+                        Some(WindowCommand::PasteAndGo)
+                    }
                     (Some(glutin::VirtualKeyCode::R), true, _, _) => Some(WindowCommand::Reload),
                     (Some(glutin::VirtualKeyCode::Left), true, _, _) => {
                         Some(WindowCommand::NavigateBack)
@@ -102,6 +131,11 @@ impl GlutinWindow {
                     _ => None,
                 }
             }
+            glutin::WindowEvent::MouseInput {
+                state: glutin::ElementState::Pressed,
+                button,
+                ..
+            } => navigation_button(button),
             _ => None,
         }
     }
@@ -132,17 +166,20 @@ impl GlutinWindow {
                 };
                 Some(ViewEvent::MouseWheel(delta, phase))
             }
-            glutin::WindowEvent::MouseInput {
-                state,
-                button: glutin::MouseButton::Left,
-                ..
-            } => {
+            glutin::WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    glutin::MouseButton::Left => MouseButton::Left,
+                    glutin::MouseButton::Right => MouseButton::Right,
+                    glutin::MouseButton::Middle => MouseButton::Middle,
+                    // Back and forward buttons are handled as commands
+                    glutin::MouseButton::Other(_) => return None,
+                };
                 let state = match state {
                     glutin::ElementState::Released => ElementState::Released,
                     glutin::ElementState::Pressed => ElementState::Pressed,
                 };
                 Some(ViewEvent::MouseInput(state,
-                                           MouseButton::Left,
+                                           button,
                                            self.mouse_coordinate.0,
                                            self.mouse_coordinate.1))
             }
@@ -38,7 +38,7 @@ impl ViewMethods for View {
     fn get_geometry(&self) -> DrawableGeometry {
         let windows = self.windows.borrow();
         let win = windows.get(&self.id).unwrap();
-        let (mut width, mut height) = win.glutin_window
+        let (width, height) = win.glutin_window
             .get_inner_size()
             .expect("Failed to get window inner size.");
 
@@ -47,8 +47,9 @@ impl ViewMethods for View {
         #[cfg(not(target_os = "windows"))]
         let factor = 1.0;
 
-        width /= factor as u32;
-        height /= factor as u32;
+        // Not `factor as u32`: that would turn 1.5 into 1
+        let width = (width as f32 / factor).round() as u32;
+        let height = (height as f32 / factor).round() as u32;
 
         DrawableGeometry {
             view_size: (width, height),
@@ -75,6 +76,19 @@ impl ViewMethods for View {
     // FIXME: should be controlled by state
     fn exit_fullscreen(&self) {}
 
+    fn set_inner_size(&self, width: u32, height: u32) {
+        let windows = self.windows.borrow();
+        let win = windows.get(&self.id).unwrap();
+        #[cfg(target_os = "windows")]
+        let factor = super::utils::windows_hidpi_factor();
+        #[cfg(not(target_os = "windows"))]
+        let factor = 1.0;
+
+        // Will trigger a Resized event
+        win.glutin_window
+            .set_inner_size((width as f32 * factor) as u32, (height as f32 * factor) as u32);
+    }
+
     fn set_live_resize_callback(&self, _callback: &FnMut()) {
         // FIXME
     }
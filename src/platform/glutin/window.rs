@@ -31,7 +31,7 @@ impl Window {
     }
 
     fn render_title(&self, state: &WindowState) {
-        let text = state
+        let tabs = state
             .tabs
             .alive_browsers()
             .iter()
@@ -40,10 +40,23 @@ impl Window {
                     .as_ref()
                     .and_then(|t| if t.is_empty() { None } else { Some(t) })
                     .map_or("No Title", |t| t.as_str());
-                let selected = if !b.is_background { '>' } else { ' ' };
-                let loading = if b.is_loading { '*' } else { ' ' };
-                format!("{} {} {:15.15} {}|", f, selected, title, loading)
+                let title = format!("{:1$.1$}", title, state.tab_title.max_length);
+                let selected = if !b.is_background { ">" } else { " " };
+                let loading = if b.is_loading { "*" } else { " " };
+                let tab = state
+                    .tab_title
+                    .template
+                    .replace("{selected}", selected)
+                    .replace("{loading}", loading)
+                    .replace("{url}", b.url.as_ref().map_or("", |u| u.as_str()))
+                    .replace("{title}", &title);
+                format!("{} {}|", f, tab)
             });
+        let text = if state.private {
+            format!("Private {}", tabs)
+        } else {
+            tabs
+        };
         let mut windows = self.windows.borrow_mut();
         windows
             .get_mut(&self.id)
@@ -71,6 +84,48 @@ impl Window {
                 .push(WindowEvent::UrlbarFocusChanged(false));
         }
     }
+
+    fn render_context_menu(&self, state: &WindowState) {
+        if let Some(ref menu) = state.context_menu {
+            let message = menu.items
+                .iter()
+                .enumerate()
+                .fold(String::new(),
+                      |m, (i, item)| format!("{}{}. {}\n", m, i + 1, item.label()));
+            let mut windows = self.windows.borrow_mut();
+            let win = windows.get_mut(&self.id).unwrap();
+            let item = tinyfiledialogs::input_box("Context Menu", &message, "1")
+                .and_then(|choice| choice.trim().parse::<usize>().ok())
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| menu.items.get(i));
+            if let Some(item) = item {
+                win.window_events
+                    .push(WindowEvent::ContextMenuItemSelected(item.clone()));
+            }
+            win.window_events.push(WindowEvent::ContextMenuClosed);
+        }
+    }
+
+    fn render_command_palette(&self, state: &WindowState) {
+        // No list widget here. The best match is run.
+        if state.command_palette.is_some() {
+            let mut windows = self.windows.borrow_mut();
+            let win = windows.get_mut(&self.id).unwrap();
+            match tinyfiledialogs::input_box("Command Palette",
+                                             "Search commands, tabs, bookmarks and history",
+                                             "") {
+                Some(query) => {
+                    win.window_events
+                        .push(WindowEvent::CommandPaletteQueryChanged(query));
+                    win.window_events
+                        .push(WindowEvent::CommandPaletteSubmit(0));
+                }
+                None => {}
+            }
+            win.window_events
+                .push(WindowEvent::CommandPaletteClosed);
+        }
+    }
 }
 
 impl WindowMethods for Window {
@@ -89,22 +144,38 @@ impl WindowMethods for Window {
                     match keys.as_slice() {
                         &[K::tabs, K::Index(_), K::Alive, K::is_background] |
                         &[K::tabs, K::Index(_), K::Alive, K::is_loading] |
-                        &[K::tabs, K::Index(_), K::Alive, K::title] => {
+                        &[K::tabs, K::Index(_), K::Alive, K::title] |
+                        &[K::tab_title, _..] => {
                             self.render_title(state);
                         }
 
                         &[K::status] |
+                        &[K::debug_options, _..] |
+                        &[K::sidebar_is_open] |
+                        &[K::options_open] |
+                        &[K::logs_visible] |
                         &[K::tabs, K::Index(_), K::Alive, K::url] |
                         &[K::tabs, K::Index(_), K::Alive, K::can_go_back] |
                         &[K::tabs, K::Index(_), K::Alive, K::can_go_forward] |
                         &[K::tabs, K::Index(_), K::Alive, K::zoom] |
-                        &[K::tabs, K::Index(_), K::Alive, K::user_input] => {
+                        &[K::tabs, K::Index(_), K::Alive, K::user_input] |
+                        &[K::tabs, K::Index(_), K::Alive, K::blocked_count] => {
                             // Nothing to do
                         }
                         &[K::tabs, K::Index(i), K::Alive, K::urlbar_focused] if i == idx => {
                             self.render_urlbar(current_browser_state);
                         }
-                        _ => println!("Window::render: unexpected Modified keys: {:?}", keys),
+                        &[K::command_palette] => {
+                            self.render_command_palette(state);
+                        }
+                        &[K::context_menu] => {
+                            self.render_context_menu(state);
+                        }
+                        &[K::command_palette, _..] |
+                        &[K::context_menu, _..] => {
+                            // Nothing to do
+                        }
+                        _ => warn!("Window::render: unexpected Modified keys: {:?}", keys),
                     }
                 }
                 ChangeType::Added(keys) => {
@@ -112,7 +183,11 @@ impl WindowMethods for Window {
                         &[K::tabs, K::Index(_)] => {
                             self.render_title(state);
                         }
-                        _ => println!("Window::render: unexpected Added keys: {:?}", keys),
+                        &[K::command_palette, _..] |
+                        &[K::context_menu, _..] => {
+                            // Nothing to do
+                        }
+                        _ => warn!("Window::render: unexpected Added keys: {:?}", keys),
                     }
                 }
                 ChangeType::Removed(keys) => {
@@ -120,7 +195,11 @@ impl WindowMethods for Window {
                         &[K::tabs, K::Index(_), K::Alive] => {
                             self.render_title(state);
                         }
-                        _ => println!("Window::render: unexpected Removed keys: {:?}", keys),
+                        &[K::command_palette, _..] |
+                        &[K::context_menu, _..] => {
+                            // Nothing to do
+                        }
+                        _ => warn!("Window::render: unexpected Removed keys: {:?}", keys),
                     }
                 }
             }
@@ -147,5 +226,10 @@ impl WindowMethods for Window {
         events
     }
 
-    fn append_logs(&self, _logs: &Vec<ShellLog>) {}
+    /// No logs panel: shown logs go to stderr.
+    fn append_logs(&self, logs: &Vec<ShellLog>) {
+        for l in logs {
+            eprintln!("{} - {}: {}", l.level, l.target, l.message);
+        }
+    }
 }
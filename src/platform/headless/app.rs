@@ -0,0 +1,140 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use osmesa_sys;
+use platform;
+use state::{AppState, ChangeType, WindowState};
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::{c_int, c_void};
+use std::path::PathBuf;
+use std::ptr;
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
+use super::{make_current, HeadlessEventLoopWaker, HeadlessWindow, Window};
+use traits::app::{AppEvent, AppMethods};
+use traits::view::gl;
+use traits::window::{WindowEvent, WindowMethods};
+
+pub struct App {
+    wakeup: Arc<(Mutex<bool>, Condvar)>,
+    windows: RefCell<Vec<Rc<RefCell<HeadlessWindow>>>>,
+}
+
+impl AppMethods for App {
+    fn new<'a>(_state: &AppState) -> Result<App, &'a str> {
+        osmesa_sys::OsMesa::try_loading().map_err(|_| "Can't load OSMesa")?;
+        Ok(App {
+               wakeup: Arc::new((Mutex::new(false), Condvar::new())),
+               windows: RefCell::new(Vec::new()),
+           })
+    }
+
+    fn get_name() -> &'static str {
+        "headless"
+    }
+
+    fn get_resources_path() -> Option<PathBuf> {
+        // Same layout as the native platform
+        platform::App::get_resources_path()
+    }
+
+    fn render(&self, _diff: Vec<ChangeType>, _state: &AppState) {
+        // Nothing to render
+    }
+
+    fn get_events(&self) -> Vec<AppEvent> {
+        vec![]
+    }
+
+    fn new_window<'a>(&self, state: &WindowState) -> Result<Box<WindowMethods>, &'a str> {
+        let (width, height) = state.inner_size;
+
+        let attributes = [osmesa_sys::OSMESA_FORMAT,
+                          osmesa_sys::OSMESA_RGBA as c_int,
+                          osmesa_sys::OSMESA_DEPTH_BITS,
+                          24,
+                          osmesa_sys::OSMESA_STENCIL_BITS,
+                          8,
+                          osmesa_sys::OSMESA_PROFILE,
+                          osmesa_sys::OSMESA_CORE_PROFILE,
+                          osmesa_sys::OSMESA_CONTEXT_MAJOR_VERSION,
+                          3,
+                          osmesa_sys::OSMESA_CONTEXT_MINOR_VERSION,
+                          2,
+                          0];
+        let context = unsafe {
+            osmesa_sys::OSMesaCreateContextAttribs(attributes.as_ptr(), ptr::null_mut())
+        };
+        if context.is_null() {
+            return Err("Can't create OSMesa context");
+        }
+
+        let mut buffer = vec![0u8; (width * height * 4) as usize];
+        if !make_current(context, &mut buffer, width, height) {
+            unsafe { osmesa_sys::OSMesaDestroyContext(context) };
+            return Err("Couldn't make OSMesa context current");
+        }
+
+        let gl = unsafe {
+            gl::GlFns::load_with(|s| {
+                let s = CString::new(s).unwrap();
+                osmesa_sys::OSMesaGetProcAddress(s.as_ptr())
+                    .map_or(ptr::null(), |f| f as *const c_void)
+            })
+        };
+
+        gl.clear_color(1.0, 1.0, 1.0, 1.0);
+        gl.clear(gl::COLOR_BUFFER_BIT);
+        gl.finish();
+
+        let window = Rc::new(RefCell::new(HeadlessWindow {
+                                              gl,
+                                              context,
+                                              buffer,
+                                              size: (width, height),
+                                              event_loop_waker: box HeadlessEventLoopWaker {
+                                                  wakeup: self.wakeup.clone(),
+                                              },
+                                              view_events: vec![],
+                                              window_events: vec![],
+                                          }));
+
+        self.windows.borrow_mut().push(window.clone());
+
+        Ok(Box::new(Window::new(state, window)))
+    }
+
+    fn run<T>(&self, mut callback: T)
+        where T: FnMut()
+    {
+        callback();
+        loop {
+            {
+                // Blocks until woken up
+                let &(ref lock, ref cvar) = &*self.wakeup;
+                let mut awaken = lock.lock().unwrap();
+                while !*awaken {
+                    awaken = cvar.wait(awaken).unwrap();
+                }
+                *awaken = false;
+            }
+            for window in self.windows.borrow().iter() {
+                window
+                    .borrow_mut()
+                    .window_events
+                    .push(WindowEvent::EventLoopAwaken);
+            }
+            callback();
+        }
+    }
+}
+
+impl Drop for App {
+    fn drop(&mut self) {
+        for window in self.windows.borrow().iter() {
+            unsafe { osmesa_sys::OSMesaDestroyContext(window.borrow().context) };
+        }
+    }
+}
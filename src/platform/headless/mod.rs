@@ -0,0 +1,57 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+mod app;
+mod view;
+mod window;
+
+use osmesa_sys::{self, OSMesaContext};
+use servo::EventLoopWaker;
+use std::os::raw::{c_int, c_void};
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
+use traits::view::{gl, ViewEvent};
+use traits::window::WindowEvent;
+
+pub use self::app::App;
+pub use self::view::View;
+pub use self::window::Window;
+
+pub struct HeadlessEventLoopWaker {
+    wakeup: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl EventLoopWaker for HeadlessEventLoopWaker {
+    fn clone(&self) -> Box<EventLoopWaker + Send> {
+        box HeadlessEventLoopWaker { wakeup: self.wakeup.clone() }
+    }
+    fn wake(&self) {
+        let &(ref lock, ref cvar) = &*self.wakeup;
+        *lock.lock().unwrap() = true;
+        cvar.notify_one();
+    }
+}
+
+pub struct HeadlessWindow {
+    gl: Rc<gl::Gl>,
+    context: OSMesaContext,
+    // OSMesa renders in there. Must not be reallocated while
+    // the context is alive.
+    buffer: Vec<u8>,
+    size: (u32, u32),
+    event_loop_waker: Box<EventLoopWaker>,
+    view_events: Vec<ViewEvent>,
+    window_events: Vec<WindowEvent>,
+}
+
+/// Render into `buffer` from now on. False on failure.
+fn make_current(context: OSMesaContext, buffer: &mut Vec<u8>, width: u32, height: u32) -> bool {
+    unsafe {
+        osmesa_sys::OSMesaMakeCurrent(context,
+                                      buffer.as_mut_ptr() as *mut c_void,
+                                      gl::UNSIGNED_BYTE,
+                                      width as c_int,
+                                      height as c_int) != 0
+    }
+}
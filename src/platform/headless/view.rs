@@ -0,0 +1,65 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use super::{make_current, HeadlessWindow};
+use traits::view::*;
+
+pub struct View {
+    window: Rc<RefCell<HeadlessWindow>>,
+}
+
+impl View {
+    pub fn new(window: Rc<RefCell<HeadlessWindow>>) -> View {
+        View { window }
+    }
+}
+
+impl ViewMethods for View {
+    fn get_geometry(&self) -> DrawableGeometry {
+        DrawableGeometry {
+            view_size: self.window.borrow().size,
+            margins: (0, 0, 0, 0),
+            position: (0, 0),
+            hidpi_factor: 1.0,
+        }
+    }
+
+    fn update_drawable(&self) {
+        // Fixed size
+    }
+
+    fn enter_fullscreen(&self) {}
+
+    fn exit_fullscreen(&self) {}
+
+    fn set_inner_size(&self, width: u32, height: u32) {
+        let mut window = self.window.borrow_mut();
+        let mut buffer = vec![0u8; (width * height * 4) as usize];
+        if make_current(window.context, &mut buffer, width, height) {
+            // The previous buffer isn't used anymore
+            window.buffer = buffer;
+            window.size = (width, height);
+            window.view_events.push(ViewEvent::GeometryDidChange);
+        } else {
+            warn!("Can't resize OSMesa buffer to {}x{}", width, height);
+        }
+    }
+
+    fn set_live_resize_callback(&self, _callback: &FnMut()) {}
+
+    fn gl(&self) -> Rc<gl::Gl> {
+        self.window.borrow().gl.clone()
+    }
+
+    fn get_events(&self) -> Vec<ViewEvent> {
+        self.window.borrow_mut().view_events.drain(..).collect()
+    }
+
+    fn swap_buffers(&self) {
+        // Nothing to swap. Make sure the buffer is up to date.
+        self.window.borrow().gl.finish();
+    }
+}
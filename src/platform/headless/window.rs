@@ -0,0 +1,42 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use logs::ShellLog;
+use servo::EventLoopWaker;
+use state::{ChangeType, WindowState};
+use std::cell::RefCell;
+use std::rc::Rc;
+use super::{HeadlessWindow, View};
+use traits::view::ViewMethods;
+use traits::window::{WindowEvent, WindowMethods};
+
+pub struct Window {
+    window: Rc<RefCell<HeadlessWindow>>,
+}
+
+impl Window {
+    pub fn new(_state: &WindowState, window: Rc<RefCell<HeadlessWindow>>) -> Window {
+        Window { window }
+    }
+}
+
+impl WindowMethods for Window {
+    fn render(&self, _diff: Vec<ChangeType>, _state: &WindowState) {
+        // Nothing to render
+    }
+
+    fn new_view(&self) -> Result<Rc<ViewMethods>, &'static str> {
+        Ok(Rc::new(View::new(self.window.clone())))
+    }
+
+    fn new_event_loop_waker(&self) -> Box<EventLoopWaker> {
+        self.window.borrow().event_loop_waker.clone()
+    }
+
+    fn get_events(&self) -> Vec<WindowEvent> {
+        self.window.borrow_mut().window_events.drain(..).collect()
+    }
+
+    fn append_logs(&self, _logs: &Vec<ShellLog>) {}
+}
@@ -11,3 +11,6 @@ mod platform;
 #[cfg(any(feature = "force-glutin", not(target_os = "macos")))]
 #[path="glutin/mod.rs"]
 mod platform;
+
+// Offscreen, no window system required. Selected at runtime with --headless.
+pub mod headless;
@@ -0,0 +1,347 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A profile is a directory holding everything that persists between runs:
+//!
+//! ```text
+//! servo/               Servo's cookies, local storage and HTTP cache
+//! config.json          User settings (see config.rs)
+//! debug_options.json
+//! history.json
+//! bookmarks.json
+//! session.json         URLs of the open tabs
+//! blocklists/          Content blocking lists (see blocker.rs)
+//! blocking_exceptions.json
+//! lock                 Locked by the instance using the profile, with its PID
+//! ```
+//!
+//! Named profiles live in the `profiles` directory of the platform's config
+//! directory, see `storage::config_dir`.
+//!
+//! The lock is an OS file lock (flock, LockFileEx): the system releases it
+//! when the process ends, however it ends.
+//!
+//! Private windows don't write to the profile. Servo's data goes to a
+//! temporary directory instead, see `PrivateStorage`.
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::os::raw::c_int;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use storage;
+
+pub const DEFAULT_PROFILE: &'static str = "default";
+
+pub const CONFIG_FILE: &'static str = "config.json";
+pub const DEBUG_OPTIONS_FILE: &'static str = "debug_options.json";
+pub const HISTORY_FILE: &'static str = "history.json";
+pub const BOOKMARKS_FILE: &'static str = "bookmarks.json";
+pub const SESSION_FILE: &'static str = "session.json";
+pub const BLOCKLISTS_DIR: &'static str = "blocklists";
+pub const BLOCKING_EXCEPTIONS_FILE: &'static str = "blocking_exceptions.json";
+
+const SERVO_DIR: &'static str = "servo";
+const LOCK_FILE: &'static str = "lock";
+const PRIVATE_PREFIX: &'static str = "servoshell-private-";
+
+pub struct Profile {
+    dir: PathBuf,
+    // Held until the process ends. None: read only
+    lock: Option<FileLock>,
+}
+
+impl Profile {
+    /// Create the directory if needed, and lock it. Fails if another
+    /// instance uses it.
+    pub fn open(dir: PathBuf) -> Result<Profile, String> {
+        fs::create_dir_all(dir.join(SERVO_DIR))
+            .map_err(|e| format!("Can't create profile {}: {}", dir.display(), e))?;
+        let path = dir.join(LOCK_FILE);
+        let lock = match FileLock::new(&path)? {
+            Some(lock) => lock,
+            None => {
+                let pid = FileLock::owner(&path).map_or("unknown".to_owned(), |p| p.to_string());
+                return Err(format!("Profile {} is used by another instance (pid {})",
+                                   dir.display(),
+                                   pid));
+            }
+        };
+        Ok(Profile {
+               dir,
+               lock: Some(lock),
+           })
+    }
+
+    /// For private windows: nothing is created, locked or saved. Another
+    /// instance can use it at the same time.
+    pub fn read_only(dir: PathBuf) -> Profile {
+        Profile { dir, lock: None }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where Servo keeps its data.
+    pub fn servo_dir(&self) -> PathBuf {
+        self.dir.join(SERVO_DIR)
+    }
+
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    pub fn load<T: DeserializeOwned>(&self, file: &str) -> Option<T> {
+        storage::load(&self.path(file))
+    }
+
+    pub fn save<T: Serialize>(&self, file: &str, value: &T) {
+        if self.lock.is_none() {
+            warn!("Not saving {}: the profile is read only", file);
+            return;
+        }
+        storage::save(&self.path(file), value)
+    }
+}
+
+/// A lock file, locked for as long as the value lives.
+struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// None if another process holds it.
+    fn new(path: &Path) -> Result<Option<FileLock>, String> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| format!("Can't open {}: {}", path.display(), e))?;
+        if !try_lock(&file).map_err(|e| format!("Can't lock {}: {}", path.display(), e))? {
+            return Ok(None);
+        }
+        // Only for the error message of the other instances
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| write!(file, "{}", process::id()))
+            .map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+        Ok(Some(FileLock { _file: file }))
+    }
+
+    fn owner(path: &Path) -> Option<u32> {
+        let mut pid = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut pid))
+            .ok()?;
+        pid.trim().parse().ok()
+    }
+}
+
+/// False if another process has the lock.
+#[cfg(unix)]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use libc;
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+#[cfg(windows)]
+fn try_lock(file: &File) -> io::Result<bool> {
+    use kernel32;
+    use std::mem;
+    use std::os::windows::io::AsRawHandle;
+    use winapi;
+
+    let locked = unsafe {
+        let mut overlapped: winapi::OVERLAPPED = mem::zeroed();
+        kernel32::LockFileEx(file.as_raw_handle() as winapi::HANDLE,
+                             winapi::LOCKFILE_EXCLUSIVE_LOCK | winapi::LOCKFILE_FAIL_IMMEDIATELY,
+                             0,
+                             !0,
+                             !0,
+                             &mut overlapped)
+    };
+    if locked != 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(winapi::ERROR_LOCK_VIOLATION as i32) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+fn profiles_dir() -> Result<PathBuf, String> {
+    storage::config_dir()
+        .map(|dir| dir.join("profiles"))
+        .ok_or("Can't find the config directory".to_owned())
+}
+
+/// Directory of a named profile.
+pub fn named(name: &str) -> Result<PathBuf, String> {
+    let valid = !name.is_empty() &&
+                name.chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(format!("Invalid profile name `{}`: use letters, digits, - and _", name));
+    }
+    Ok(profiles_dir()?.join(name))
+}
+
+pub fn list() -> Result<Vec<String>, String> {
+    let dir = profiles_dir()?;
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .map_err(|e| format!("Can't read {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+pub fn create(name: &str) -> Result<(), String> {
+    let dir = named(name)?;
+    if dir.exists() {
+        return Err(format!("Profile `{}` already exists", name));
+    }
+    fs::create_dir_all(dir.join(SERVO_DIR))
+        .map_err(|e| format!("Can't create {}: {}", dir.display(), e))
+}
+
+pub fn delete(name: &str) -> Result<(), String> {
+    let dir = named(name)?;
+    if !dir.exists() {
+        return Err(format!("Profile `{}` doesn't exist", name));
+    }
+    // Makes sure it's not in use
+    drop(Profile::open(dir.clone())?);
+    fs::remove_dir_all(&dir).map_err(|e| format!("Can't delete {}: {}", dir.display(), e))
+}
+
+/// Temporary directory for Servo's data while private windows are open.
+/// Wiped when they are all closed, and when the process exits, however it
+/// exits. What a crash leaves behind is wiped by the next private window.
+///
+/// `servoshell-private-<random>.lock`, next to the directory, is locked
+/// while it's in use. Outside of it, so that the directory can be wiped
+/// while the lock is held (Windows can't delete a file that is open).
+pub struct PrivateStorage {
+    dir: PathBuf,
+    // Taken to remove the lock file
+    lock: Option<FileLock>,
+}
+
+impl PrivateStorage {
+    pub fn new() -> Result<PrivateStorage, String> {
+        wipe_stale_private_dirs();
+        let dir = storage::create_temp_dir(PRIVATE_PREFIX)?;
+        let lock = FileLock::new(&lock_path(&dir))?
+            .ok_or_else(|| format!("{} is used by another instance", dir.display()))?;
+        wipe_at_exit(&dir);
+        Ok(PrivateStorage {
+               dir,
+               lock: Some(lock),
+           })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn wipe(&self) {
+        wipe(&self.dir);
+    }
+}
+
+impl Drop for PrivateStorage {
+    fn drop(&mut self) {
+        self.wipe();
+        self.lock.take();
+        fs::remove_file(lock_path(&self.dir)).ok();
+    }
+}
+
+fn lock_path(dir: &Path) -> PathBuf {
+    let mut name = dir.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+fn wipe(dir: &Path) {
+    if dir.exists() {
+        if let Err(err) = fs::remove_dir_all(dir) {
+            warn!("Can't wipe {}: {}", dir.display(), err);
+        }
+    }
+}
+
+// Box<PathBuf> of the private directory, or 0
+static AT_EXIT_DIR: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// `process::exit`, Cocoa's terminate and returning from main all go
+/// through the C library's `exit`, which runs `atexit` handlers. Destructors
+/// don't run in the first two cases.
+fn wipe_at_exit(dir: &Path) {
+    extern "C" {
+        fn atexit(callback: extern "C" fn()) -> c_int;
+    }
+    extern "C" fn wipe_private_dir() {
+        let dir = AT_EXIT_DIR.swap(0, Ordering::SeqCst);
+        if dir != 0 {
+            let dir = unsafe { Box::from_raw(dir as *mut PathBuf) };
+            // Not logged, the logger might be gone
+            fs::remove_dir_all(&*dir).ok();
+        }
+    }
+    let dir = Box::into_raw(Box::new(dir.to_owned())) as usize;
+    if AT_EXIT_DIR.swap(dir, Ordering::SeqCst) == 0 {
+        if unsafe { atexit(wipe_private_dir) } != 0 {
+            warn!("Can't wipe the private data at exit");
+        }
+    }
+}
+
+/// Private data left behind by instances that crashed.
+fn wipe_stale_private_dirs() {
+    let entries = match fs::read_dir(env::temp_dir()) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        if !name.starts_with(PRIVATE_PREFIX) || !name.ends_with(".lock") {
+            continue;
+        }
+        // Locked: still in use
+        let lock = entry.path();
+        if let Ok(Some(lock_file)) = FileLock::new(&lock) {
+            wipe(&lock.with_extension(""));
+            drop(lock_file);
+            fs::remove_file(&lock).ok();
+        }
+    }
+}
@@ -0,0 +1,429 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Reftest runner. A manifest lists pairs of URLs that must (`==`) or must
+//! not (`!=`) render the same:
+//!
+//! ```text
+//! # Comments start with #, at the start of a line or after a space.
+//! # Paths are relative to the manifest.
+//! == test.html reference.html
+//! != test.html reference.html
+//! fuzzy(2,100) == test.html reference.html
+//! ```
+//!
+//! `fuzzy(max_difference,max_pixels)` tolerates up to `max_pixels` pixels
+//! with a channel differing by at most `max_difference`.
+//!
+//! Tests render at `WINDOW_SIZE`, whatever the config says, so that the
+//! results don't depend on the profile.
+
+use capture::Frame;
+use servo::{BrowserId, Servo, ServoEvent, ServoUrl};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+pub const WINDOW_SIZE: (u32, u32) = (800, 1000);
+pub const DEFAULT_OUTPUT_DIR: &'static str = "reftest-results";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Relation {
+    Equal,
+    NotEqual,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Fuzz {
+    max_difference: u8,
+    max_pixels: usize,
+}
+
+#[derive(Debug)]
+struct Reftest {
+    relation: Relation,
+    fuzz: Fuzz,
+    test: ServoUrl,
+    reference: ServoUrl,
+}
+
+struct Comparison {
+    max_difference: u8,
+    different_pixels: usize,
+    diff: Option<Frame>,
+}
+
+struct Outcome {
+    passed: bool,
+    message: String,
+}
+
+pub struct Runner {
+    tests: Vec<Reftest>,
+    current: usize,
+    // Render of the current test, while its reference loads
+    test_frame: Option<Frame>,
+    outcomes: Vec<Outcome>,
+    output_dir: PathBuf,
+}
+
+impl Runner {
+    pub fn new(manifest: &Path, output_dir: PathBuf) -> Result<Runner, String> {
+        let tests = parse_manifest(manifest)?;
+        if tests.is_empty() {
+            return Err(format!("No test in {}", manifest.display()));
+        }
+        fs::create_dir_all(&output_dir)
+            .map_err(|e| format!("Can't create {}: {}", output_dir.display(), e))?;
+        Ok(Runner {
+               tests,
+               current: 0,
+               test_frame: None,
+               outcomes: Vec::new(),
+               output_dir,
+           })
+    }
+
+    /// URL to load first.
+    pub fn first_url(&self) -> String {
+        self.tests[0].test.as_str().to_owned()
+    }
+
+    /// Drives the run. Returns the exit code once all the tests are done.
+    pub fn handle_event(&mut self,
+                        servo: &Servo,
+                        browser: BrowserId,
+                        event: &ServoEvent)
+                        -> Option<i32> {
+        match *event {
+            ServoEvent::LoadEnd(id) if id == browser => {
+                // FIXME: LoadEnd doesn't guarantee that webfonts and images
+                // are painted. Servo's own harness waits for the compositor
+                // to be "ready to save image", which isn't exposed.
+                servo.capture_frame();
+                None
+            }
+            ServoEvent::FrameCaptured(ref frame) => {
+                match self.test_frame.take() {
+                    None => {
+                        self.test_frame = Some(frame.clone());
+                        let reference = self.tests[self.current].reference.clone();
+                        servo.load_url(browser, reference);
+                        None
+                    }
+                    Some(test_frame) => {
+                        let outcome = self.check(&test_frame, frame);
+                        println!("{}", outcome.message);
+                        self.outcomes.push(outcome);
+                        self.current += 1;
+                        if self.current < self.tests.len() {
+                            let test = self.tests[self.current].test.clone();
+                            servo.load_url(browser, test);
+                            None
+                        } else {
+                            Some(self.finish())
+                        }
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn check(&self, test_frame: &Frame, reference_frame: &Frame) -> Outcome {
+        let test = &self.tests[self.current];
+        let name = format!("{} {} {}",
+                           test.test,
+                           if test.relation == Relation::Equal {
+                               "=="
+                           } else {
+                               "!="
+                           },
+                           test.reference);
+
+        let comparison = match compare(test_frame, reference_frame) {
+            Ok(comparison) => comparison,
+            Err(err) => {
+                return Outcome {
+                           passed: false,
+                           message: format!("FAIL {} ({})", name, err),
+                       }
+            }
+        };
+
+        let matches = comparison.different_pixels == 0 ||
+                      (comparison.max_difference <= test.fuzz.max_difference &&
+                       comparison.different_pixels <= test.fuzz.max_pixels);
+        let passed = matches == (test.relation == Relation::Equal);
+        let details = format!("max difference: {}, different pixels: {}",
+                              comparison.max_difference,
+                              comparison.different_pixels);
+
+        if passed {
+            return Outcome {
+                       passed,
+                       message: format!("PASS {} ({})", name, details),
+                   };
+        }
+
+        // Keep the renders to investigate the failure
+        let prefix = self.output_dir.join(format!("{:03}", self.current + 1));
+        let mut images = vec![(test_frame, "test"), (reference_frame, "reference")];
+        if let Some(ref diff) = comparison.diff {
+            images.push((diff, "diff"));
+        }
+        for (frame, suffix) in images {
+            let path = prefix.with_extension(format!("{}.png", suffix));
+            if let Err(err) = frame.write_png(&path) {
+                warn!("Can't write {}: {}", path.display(), err);
+            }
+        }
+
+        Outcome {
+            passed,
+            message: format!("FAIL {} ({}, images: {}.*.png)",
+                             name,
+                             details,
+                             prefix.display()),
+        }
+    }
+
+    fn finish(&self) -> i32 {
+        let passed = self.outcomes.iter().filter(|o| o.passed).count();
+        let failed = self.outcomes.len() - passed;
+        let summary = format!("{} passed, {} failed", passed, failed);
+        println!("{}", summary);
+
+        let path = self.output_dir.join("report.txt");
+        let result = File::create(&path).and_then(|mut file| {
+            for outcome in &self.outcomes {
+                writeln!(file, "{}", outcome.message)?;
+            }
+            writeln!(file, "{}", summary)
+        });
+        match result {
+            Ok(()) => println!("Report written to {}", path.display()),
+            Err(err) => warn!("Can't write {}: {}", path.display(), err),
+        }
+
+        if failed == 0 { 0 } else { 1 }
+    }
+}
+
+/// Compare two frames pixel by pixel. The diff image shows the test render
+/// faded out, and the different pixels in red.
+fn compare(a: &Frame, b: &Frame) -> Result<Comparison, String> {
+    if a.width != b.width || a.height != b.height {
+        return Err(format!("size mismatch: {}x{} vs {}x{}",
+                           a.width,
+                           a.height,
+                           b.width,
+                           b.height));
+    }
+
+    let mut max_difference = 0;
+    let mut different_pixels = 0;
+    let mut diff = Vec::with_capacity(a.pixels.len());
+
+    for (pa, pb) in a.pixels.chunks(4).zip(b.pixels.chunks(4)) {
+        let difference = pa.iter()
+            .zip(pb.iter())
+            .map(|(ca, cb)| (*ca as i16 - *cb as i16).abs() as u8)
+            .max()
+            .unwrap_or(0);
+        if difference > 0 {
+            different_pixels += 1;
+            if difference > max_difference {
+                max_difference = difference;
+            }
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            diff.extend(pa[..3].iter().map(|c| 192 + c / 4));
+            diff.push(255);
+        }
+    }
+
+    Ok(Comparison {
+           max_difference,
+           different_pixels,
+           diff: if different_pixels > 0 {
+               Some(Frame {
+                        width: a.width,
+                        height: a.height,
+                        pixels: diff,
+                    })
+           } else {
+               None
+           },
+       })
+}
+
+fn parse_manifest(manifest: &Path) -> Result<Vec<Reftest>, String> {
+    let file = File::open(manifest)
+        .map_err(|e| format!("Can't open {}: {}", manifest.display(), e))?;
+    let base = manifest
+        .canonicalize()
+        .map_err(|e| format!("Can't resolve {}: {}", manifest.display(), e))?;
+    let base = base.parent().unwrap_or(Path::new("/"));
+    parse_lines(manifest, base, BufReader::new(file))
+}
+
+fn parse_lines<R: BufRead>(manifest: &Path,
+                           base: &Path,
+                           lines: R)
+                           -> Result<Vec<Reftest>, String> {
+    let mut tests = Vec::new();
+    for (idx, line) in lines.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = strip_comment(&line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |what: &str| format!("{}:{}: {}", manifest.display(), idx + 1, what);
+
+        let mut tokens: Vec<&str> = line.split_whitespace().collect();
+        let fuzz = if tokens[0].starts_with("fuzzy(") {
+            parse_fuzz(tokens.remove(0)).ok_or_else(|| error("invalid fuzzy()"))?
+        } else {
+            Fuzz::default()
+        };
+
+        let (relation, test, reference) = match tokens.as_slice() {
+            &["==", test, reference] => (Relation::Equal, test, reference),
+            &["!=", test, reference] => (Relation::NotEqual, test, reference),
+            _ => return Err(error("expected `== test reference` or `!= test reference`")),
+        };
+
+        tests.push(Reftest {
+                       relation,
+                       fuzz,
+                       test: resolve(base, test).ok_or_else(|| error("invalid test url"))?,
+                       reference: resolve(base, reference)
+                           .ok_or_else(|| error("invalid reference url"))?,
+                   });
+    }
+    Ok(tests)
+}
+
+/// A `#` starts a comment at the start of the line or after a space. In
+/// a URL, it's a fragment.
+fn strip_comment(line: &str) -> &str {
+    let mut previous = None;
+    for (idx, c) in line.char_indices() {
+        if c == '#' && previous.map_or(true, char::is_whitespace) {
+            return &line[..idx];
+        }
+        previous = Some(c);
+    }
+    line
+}
+
+fn parse_fuzz(token: &str) -> Option<Fuzz> {
+    let args = token.trim_left_matches("fuzzy(").trim_right_matches(')');
+    let mut args = args.split(',');
+    let max_difference = args.next()?.trim().parse().ok()?;
+    let max_pixels = args.next()?.trim().parse().ok()?;
+    if args.next().is_some() {
+        return None;
+    }
+    Some(Fuzz {
+             max_difference,
+             max_pixels,
+         })
+}
+
+/// Absolute URLs are kept as is, anything else is a path relative to
+/// the manifest, with an optional fragment.
+fn resolve(base: &Path, url: &str) -> Option<ServoUrl> {
+    if let Ok(url) = ServoUrl::parse(url) {
+        return Some(url);
+    }
+    // from_file_path would percent-encode the `#`
+    let (path, fragment) = match url.find('#') {
+        Some(idx) => (&url[..idx], Some(&url[idx + 1..])),
+        None => (url, None),
+    };
+    let mut url = ServoUrl::from_file_path(base.join(path)).ok()?;
+    url.set_fragment(fragment);
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(manifest: &str) -> Result<Vec<Reftest>, String> {
+        parse_lines(Path::new("reftest.list"), Path::new("/tests"), manifest.as_bytes())
+    }
+
+    fn frame(pixels: &[[u8; 4]]) -> Frame {
+        Frame {
+            width: pixels.len() as u32,
+            height: 1,
+            pixels: pixels.iter().flat_map(|p| p.iter().cloned()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let tests = parse("# A comment\n\
+                           \n\
+                           == a.html a-ref.html\n\
+                           fuzzy(2,100) != b.html http://example.com/b.html # Why\n")
+                .unwrap();
+        assert_eq!(tests.len(), 2);
+        assert_eq!(tests[0].relation, Relation::Equal);
+        assert_eq!(tests[0].test.as_str(), "file:///tests/a.html");
+        assert_eq!(tests[0].reference.as_str(), "file:///tests/a-ref.html");
+        assert_eq!(tests[0].fuzz.max_pixels, 0);
+        assert_eq!(tests[1].relation, Relation::NotEqual);
+        assert_eq!(tests[1].reference.as_str(), "http://example.com/b.html");
+        assert_eq!(tests[1].fuzz.max_difference, 2);
+        assert_eq!(tests[1].fuzz.max_pixels, 100);
+    }
+
+    #[test]
+    fn test_parse_manifest_keeps_fragments() {
+        let tests = parse("== a.html#top http://example.com/#b\n").unwrap();
+        assert_eq!(tests[0].test.as_str(), "file:///tests/a.html#top");
+        assert_eq!(tests[0].reference.as_str(), "http://example.com/#b");
+    }
+
+    #[test]
+    fn test_parse_manifest_errors() {
+        assert_eq!(parse("\n== a.html\n").unwrap_err(),
+                   "reftest.list:2: expected `== test reference` or `!= test reference`");
+        assert!(parse("fuzzy(2) == a.html b.html").is_err());
+        assert!(parse("fuzzy(2,3,4) == a.html b.html").is_err());
+        assert!(parse("<= a.html b.html").is_err());
+    }
+
+    #[test]
+    fn test_compare_same() {
+        let a = frame(&[[1, 2, 3, 255], [4, 5, 6, 255]]);
+        let comparison = compare(&a, &a.clone()).unwrap();
+        assert_eq!(comparison.max_difference, 0);
+        assert_eq!(comparison.different_pixels, 0);
+        assert!(comparison.diff.is_none());
+    }
+
+    #[test]
+    fn test_compare_different() {
+        let a = frame(&[[0, 0, 0, 255], [10, 10, 10, 255], [0, 0, 0, 255]]);
+        let b = frame(&[[0, 0, 0, 255], [10, 13, 10, 255], [0, 0, 200, 255]]);
+        let comparison = compare(&a, &b).unwrap();
+        assert_eq!(comparison.max_difference, 200);
+        assert_eq!(comparison.different_pixels, 2);
+        let diff = comparison.diff.unwrap();
+        assert_eq!(&diff.pixels[..4], &[192, 192, 192, 255]);
+        assert_eq!(&diff.pixels[4..8], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_compare_size_mismatch() {
+        let a = frame(&[[0, 0, 0, 255]]);
+        let b = frame(&[[0, 0, 0, 255], [0, 0, 0, 255]]);
+        assert!(compare(&a, &b).is_err());
+    }
+}
@@ -4,8 +4,10 @@
 
 extern crate servo;
 
+
 use self::servo::config::servo_version;
 use self::servo::servo_config::opts;
+use self::servo::servo_config::prefs::{Pref, PrefValue, PREFS};
 use self::servo::servo_config::resource_files::set_resources_path;
 use self::servo::compositing::windowing::{MouseWindowEvent, WindowMethods, WindowEvent};
 use self::servo::msg::constellation_msg::TraversalDirection;
@@ -13,12 +15,19 @@ use self::servo::servo_geometry::DeviceIndependentPixel;
 use self::servo::euclid::{Point2D, ScaleFactor, Size2D, TypedPoint2D, TypedRect, TypedSize2D,
                           TypedVector2D};
 use self::servo::ipc_channel::ipc;
-use self::servo::script_traits::{LoadData, Microdata, MouseButton, TouchEventType};
+use self::servo::script_traits::{LoadData, Microdata, MouseButton, TouchEventType, TouchId};
 use self::servo::style_traits::DevicePixel;
 use self::servo::net_traits::net_error_list::NetError;
 use self::servo::webrender_api;
-use state::BrowserState;
+use blocker::Blocker;
+use capture::Frame;
+use cli::Options;
+use clipboard::Clipboard;
+use kiosk::KioskConfig;
+use shell_pages;
+use state::{BrowserState, DebugOptions};
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 pub use self::servo::BrowserId;
 pub use self::servo::compositing::compositor_thread::EventLoopWaker;
@@ -34,10 +43,8 @@ use traits::view::{self, DrawableGeometry};
 use std::rc::Rc;
 use std::cell::{Cell, RefCell};
 
-const SHELL_ISSUE_ALIAS: &'static str = "servoshell://issue/servoshell";
-const SERVO_ISSUE_ALIAS: &'static str = "servoshell://issue/servo";
-const SHELL_ISSUE_URL: &'static str = "http://github.com/paulrouget/servoshell/issues/new";
-const SERVO_ISSUE_URL: &'static str = "http://github.com/servo/servo/issues/new";
+// What the status callback reports for hovered links
+const LINK_SCHEMES: &'static [&'static str] = &["http", "https", "file", "data"];
 
 #[derive(Debug)]
 pub enum ServoEvent {
@@ -53,14 +60,47 @@ pub enum ServoEvent {
     CursorChanged(ServoCursor),
     FaviconChanged(BrowserId, ServoUrl),
     Key(Option<char>, Key, KeyModifiers),
-    OpenInDefaultBrowser(&'static str),
+    // A servoshell:// URL. True if a page navigated there, false if it
+    // was typed
+    ShellUrl(BrowserId, ServoUrl, bool),
     WriteMicrodata(String, String),
+    ScreenshotTaken(PathBuf, Result<(), String>),
+    FrameCaptured(Frame),
+    // Denied by the content blocker
+    NavigationBlocked(BrowserId, ServoUrl),
+}
+
+impl ServoEvent {
+    /// The browser this event is about, if any.
+    pub fn browser_id(&self) -> Option<BrowserId> {
+        match *self {
+            ServoEvent::TitleChanged(id, _) |
+            ServoEvent::LoadStart(id) |
+            ServoEvent::LoadEnd(id) |
+            ServoEvent::HeadParsed(id) |
+            ServoEvent::HistoryChanged(id, _, _) |
+            ServoEvent::FaviconChanged(id, _) |
+            ServoEvent::ShellUrl(id, _, _) |
+            ServoEvent::NavigationBlocked(id, _) => Some(id),
+            _ => None,
+        }
+    }
 }
 
+enum CaptureRequest {
+    // Saved as a PNG file
+    Screenshot(PathBuf),
+    // Sent back as a FrameCaptured event
+    Frame,
+}
+
+const LONG_PRESS_DURATION_MS: u64 = 500;
+
 struct LastMouseDown {
     button: view::MouseButton,
     x: i32,
     y: i32,
+    time: Instant,
 }
 
 pub struct Servo {
@@ -69,29 +109,108 @@ pub struct Servo {
     servo: RefCell<servo::Servo<ServoCallbacks>>,
     callbacks: Rc<ServoCallbacks>,
     mouse_down: RefCell<Option<LastMouseDown>>,
+    convert_mouse_to_touch: Cell<bool>,
+    // Left button is down and converted to a touch
+    touch_down: Cell<bool>,
 }
 
 impl Servo {
-    pub fn configure(path: PathBuf) {
+    /// Servo reads its options once, when it starts. Layout debug options
+    /// set here can't be changed afterward.
+    pub fn configure(path: PathBuf,
+                     debug_options: &DebugOptions,
+                     options: &Options,
+                     profile_dir: Option<PathBuf>) {
         let path = path.to_str().unwrap().to_string();
         set_resources_path(Some(path));
-        // FIXME: I forgot… is that necessary now?
-        opts::set_defaults(opts::default_opts());
+        let mut opts = opts::default_opts();
+        opts.show_debug_fragment_borders = debug_options.show_fragment_borders;
+        opts.parallel_display_list_building = debug_options.parallel_display_list_building;
+        opts.show_debug_parallel_layout = debug_options.show_parallel_layout;
+        opts.show_debug_borders = debug_options.show_tiles_borders;
+        // Mouse to touch conversion is done by the shell
+        opts.convert_mouse_to_touch = false;
+        // FIXME: no WebDriver (opts.webdriver_port) or devtools
+        // (opts.devtools_port) server until Servo can bind them to
+        // localhost. They listen on 0.0.0.0.
+        if let Some(ref user_agent) = options.user_agent {
+            opts.user_agent = user_agent.clone().into();
+        }
+        // Cookies, local storage and cache
+        opts.config_dir = profile_dir;
+        opts::set_defaults(opts);
+
+        for &(ref key, ref value) in &options.prefs {
+            // Same conversion as Servo's own --pref
+            let value = match value.as_str() {
+                "true" => PrefValue::Boolean(true),
+                "false" => PrefValue::Boolean(false),
+                _ => {
+                    value
+                        .parse()
+                        .map(PrefValue::Number)
+                        .unwrap_or_else(|_| PrefValue::String(value.clone()))
+                }
+            };
+            PREFS.set(key, value);
+        }
+    }
+
+    /// WebRender debug options can be toggled at any time.
+    pub fn apply_webrender_debug_options(&self, debug_options: &DebugOptions) {
+        if debug_options.wr_profiler {
+            self.toggle_webrender_debug_option(WebRenderDebugOption::Profiler);
+        }
+        if debug_options.wr_texture_cache_debug {
+            self.toggle_webrender_debug_option(WebRenderDebugOption::TextureCacheDebug);
+        }
+        if debug_options.wr_render_target_debug {
+            self.toggle_webrender_debug_option(WebRenderDebugOption::RenderTargetDebug);
+        }
     }
 
     pub fn version(&self) -> String {
         servo_version()
     }
 
+    /// Prefs that don't have their default value, sorted by name.
+    pub fn modified_prefs(&self) -> Vec<(String, String)> {
+        let mut prefs: Vec<(String, String)> = PREFS
+            .cloned()
+            .into_iter()
+            .filter_map(|(name, pref)| match pref {
+                            Pref::NoDefault(ref value) |
+                            Pref::WithDefault(_, Some(ref value)) => {
+                                let value = match **value {
+                                    PrefValue::Boolean(b) => b.to_string(),
+                                    PrefValue::Number(n) => n.to_string(),
+                                    PrefValue::String(ref s) => s.clone(),
+                                    PrefValue::Missing => "missing".to_owned(),
+                                };
+                                Some((name, value))
+                            }
+                            Pref::WithDefault(_, None) => None,
+                        })
+            .collect();
+        prefs.sort();
+        prefs
+    }
+
     pub fn new(geometry: DrawableGeometry,
                view: Rc<view::ViewMethods>,
-               waker: Box<EventLoopWaker>)
+               waker: Box<EventLoopWaker>,
+               clipboard: Rc<Clipboard>)
                -> Servo {
         let callbacks = Rc::new(ServoCallbacks {
                                     event_queue: RefCell::new(Vec::new()),
                                     geometry: Cell::new(geometry),
                                     waker: waker,
                                     view: view.clone(),
+                                    clipboard: clipboard,
+                                    capture: RefCell::new(None),
+                                    blocker: RefCell::new(Blocker::new()),
+                                    kiosk: RefCell::new(None),
+                                    hovered_link: RefCell::new(None),
                                 });
 
         let servo = servo::Servo::new(callbacks.clone());
@@ -101,14 +220,12 @@ impl Servo {
             servo: RefCell::new(servo),
             callbacks: callbacks,
             mouse_down: RefCell::new(None),
+            convert_mouse_to_touch: Cell::new(false),
+            touch_down: Cell::new(false),
         }
     }
 
-    pub fn new_browser(&self, url: &str) -> BrowserState {
-
-        // FIXME: unwrap
-        let url = ServoUrl::parse(url).unwrap();
-
+    pub fn new_browser(&self, url: ServoUrl) -> BrowserState {
         let (sender, receiver) = ipc::channel().unwrap();
         self.servo
             .borrow_mut()
@@ -128,6 +245,8 @@ impl Servo {
             can_go_forward: false,
             is_loading: false,
             urlbar_focused: false,
+            blocked_count: 0,
+            user_agent: opts::get().user_agent.to_string(),
         }
     }
 
@@ -135,6 +254,37 @@ impl Servo {
         self.callbacks.get_events()
     }
 
+    /// The link under the mouse, if any.
+    pub fn hovered_link(&self, id: BrowserId) -> Option<ServoUrl> {
+        match *self.callbacks.hovered_link.borrow() {
+            Some((link_id, ref url)) if link_id == id => Some(url.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn clipboard(&self) -> Rc<Clipboard> {
+        self.callbacks.clipboard.clone()
+    }
+
+    /// The next composited frame is written to `path`, and a
+    /// ScreenshotTaken event is sent.
+    pub fn screenshot(&self, path: PathBuf) {
+        self.request_capture(CaptureRequest::Screenshot(path));
+    }
+
+    /// The next composited frame is sent back with a FrameCaptured event.
+    pub fn capture_frame(&self) {
+        self.request_capture(CaptureRequest::Frame);
+    }
+
+    fn request_capture(&self, request: CaptureRequest) {
+        *self.callbacks.capture.borrow_mut() = Some(request);
+        // Make sure a new frame is composited
+        self.events_for_servo
+            .borrow_mut()
+            .push(WindowEvent::Refresh);
+    }
+
     pub fn select_browser(&self, id: BrowserId) {
         let event = WindowEvent::SelectBrowser(id);
         self.events_for_servo.borrow_mut().push(event);
@@ -160,6 +310,15 @@ impl Servo {
         self.events_for_servo.borrow_mut().push(event);
     }
 
+    /// servoshell:// URLs are handled by the shell. Sent back as a
+    /// ShellUrl event.
+    pub fn open_shell_url(&self, id: BrowserId, url: ServoUrl) {
+        self.callbacks
+            .event_queue
+            .borrow_mut()
+            .push(ServoEvent::ShellUrl(id, url, false));
+    }
+
     pub fn load_url(&self, id: BrowserId, url: ServoUrl) {
         let event = WindowEvent::LoadUrl(id, url);
         self.events_for_servo.borrow_mut().push(event);
@@ -175,8 +334,39 @@ impl Servo {
         (x, y)
     }
 
+    /// When enabled, the left button acts as a finger: mouse down, move and
+    /// up become touch down, move and up. Servo's touch handler turns
+    /// touch moves into scrolls (drag-to-scroll) unless content prevents it.
+    pub fn set_convert_mouse_to_touch(&self, enabled: bool) {
+        if !enabled && self.touch_down.get() {
+            // Don't leave a dangling touch
+            self.touch_down.set(false);
+            let (x, y) = self.mouse_down
+                .borrow()
+                .as_ref()
+                .map_or((0, 0), |m| (m.x, m.y));
+            self.push_touch(TouchEventType::Cancel, x, y);
+        }
+        self.convert_mouse_to_touch.set(enabled);
+    }
+
+    fn push_touch(&self, event_type: TouchEventType, x: i32, y: i32) {
+        // Only one finger
+        let event = WindowEvent::Touch(event_type,
+                                       TouchId(0),
+                                       TypedPoint2D::new(x as f32, y as f32));
+        self.events_for_servo.borrow_mut().push(event);
+    }
+
     pub fn perform_mouse_move(&self, x: i32, y: i32) {
         let (x, y) = self.substract_margins(x, y);
+        if self.convert_mouse_to_touch.get() {
+            // No hovering with fingers
+            if self.touch_down.get() {
+                self.push_touch(TouchEventType::Move, x, y);
+            }
+            return;
+        }
         let event = WindowEvent::MouseWindowMoveEventClass(TypedPoint2D::new(x as f32, y as f32));
         self.events_for_servo.borrow_mut().push(event);
     }
@@ -195,6 +385,20 @@ impl Servo {
         self.events_for_servo.borrow_mut().push(event);
     }
 
+    pub fn set_blocker(&self, blocker: Blocker) {
+        *self.callbacks.blocker.borrow_mut() = blocker;
+    }
+
+    /// Sites the content blocker leaves alone.
+    pub fn set_blocking_exceptions(&self, sites: &[String]) {
+        self.callbacks.blocker.borrow_mut().set_exceptions(sites);
+    }
+
+    /// Navigations outside of the allowed origins are denied.
+    pub fn set_kiosk(&self, kiosk: Option<KioskConfig>) {
+        *self.callbacks.kiosk.borrow_mut() = kiosk;
+    }
+
     pub fn update_geometry(&self, geometry: DrawableGeometry) {
         self.callbacks.geometry.set(geometry);
         let event = WindowEvent::Resize;
@@ -208,6 +412,36 @@ impl Servo {
                          mouse_button: view::MouseButton) {
 
         let (x, y) = self.substract_margins(x, y);
+
+        if self.convert_mouse_to_touch.get() && mouse_button == view::MouseButton::Left {
+            match element_state {
+                view::ElementState::Pressed => {
+                    *self.mouse_down.borrow_mut() = Some(LastMouseDown {
+                                                             x,
+                                                             y,
+                                                             button: mouse_button,
+                                                             time: Instant::now(),
+                                                         });
+                    self.touch_down.set(true);
+                    self.push_touch(TouchEventType::Down, x, y);
+                }
+                view::ElementState::Released => {
+                    if self.touch_down.get() {
+                        self.touch_down.set(false);
+                        // Down then Up is a tap, and would follow the link
+                        // the long press opens a context menu for
+                        let event = if self.long_press_at(x, y) {
+                            TouchEventType::Cancel
+                        } else {
+                            TouchEventType::Up
+                        };
+                        self.push_touch(event, x, y);
+                    }
+                }
+            }
+            return;
+        }
+
         let max_pixel_dist = 10f64;
         let button = match mouse_button {
             view::MouseButton::Left => MouseButton::Left,
@@ -220,6 +454,7 @@ impl Servo {
                                                          x,
                                                          y,
                                                          button: mouse_button,
+                                                         time: Instant::now(),
                                                      });
                 MouseWindowEvent::MouseDown(button, TypedPoint2D::new(x as f32, y as f32))
             }
@@ -232,6 +467,7 @@ impl Servo {
                              button: org_button,
                              x: org_x,
                              y: org_y,
+                             ..
                          }) if org_button == mouse_button => {
                         // Same button
                         let pixel_dist = Point2D::new(org_x, org_y) - Point2D::new(x, y);
@@ -256,6 +492,29 @@ impl Servo {
             .push(WindowEvent::MouseWindowEventClass(event));
     }
 
+    /// Whether the left button has been held down long enough, without
+    /// moving, to be considered a long press. Coordinates are the ones of
+    /// the mouse up event.
+    pub fn is_long_press(&self, x: i32, y: i32) -> bool {
+        let (x, y) = self.substract_margins(x, y);
+        self.long_press_at(x, y)
+    }
+
+    fn long_press_at(&self, x: i32, y: i32) -> bool {
+        match *self.mouse_down.borrow() {
+            Some(LastMouseDown {
+                     button: view::MouseButton::Left,
+                     x: org_x,
+                     y: org_y,
+                     time,
+                 }) => {
+                let moved = (org_x - x).abs() > 10 || (org_y - y).abs() > 10;
+                !moved && time.elapsed() >= Duration::from_millis(LONG_PRESS_DURATION_MS)
+            }
+            _ => false,
+        }
+    }
+
     pub fn zoom(&self, zoom: f32) {
         self.events_for_servo
             .borrow_mut()
@@ -303,6 +562,12 @@ struct ServoCallbacks {
     event_queue: RefCell<Vec<ServoEvent>>,
     waker: Box<EventLoopWaker>,
     view: Rc<view::ViewMethods>,
+    clipboard: Rc<Clipboard>,
+    capture: RefCell<Option<CaptureRequest>>,
+    blocker: RefCell<Blocker>,
+    kiosk: RefCell<Option<KioskConfig>>,
+    // From the status callback, which Servo also calls for other things
+    hovered_link: RefCell<Option<(BrowserId, ServoUrl)>>,
 }
 
 impl ServoCallbacks {
@@ -312,6 +577,17 @@ impl ServoCallbacks {
         let copy = events.drain(..).collect();
         copy
     }
+
+    /// Read back the content area of the framebuffer.
+    fn read_frame(&self) -> Frame {
+        let rect = self.window_rect();
+        Frame::read(&*self.view.gl(),
+                    self.framebuffer_size().height,
+                    rect.origin.x,
+                    rect.origin.y,
+                    rect.size.width,
+                    rect.size.height)
+    }
 }
 
 impl WindowMethods for ServoCallbacks {
@@ -331,18 +607,30 @@ impl WindowMethods for ServoCallbacks {
         TypedSize2D::new(scale_factor * width, scale_factor * height)
     }
 
+    // FIXME: Servo talks to the system clipboard itself, it can't go
+    // through our Clipboard trait. Without one, copy and paste in pages
+    // are disabled.
     fn supports_clipboard(&self) -> bool {
-        // FIXME
-        false
-    }
-
-    fn allow_navigation(&self, _id: BrowserId, url: ServoUrl, chan: ipc::IpcSender<bool>) {
-        if url.as_str() == SHELL_ISSUE_ALIAS {
-            let event = ServoEvent::OpenInDefaultBrowser(SHELL_ISSUE_URL);
+        self.clipboard.is_native()
+    }
+
+    // FIXME: only top-level navigations go through here. Servo has no hook
+    // for subresource loads yet, so the blocker can't see them.
+    fn allow_navigation(&self, id: BrowserId, url: ServoUrl, chan: ipc::IpcSender<bool>) {
+        // Shell pages can open other apps and change settings: never in kiosk mode
+        let kiosk_denies = self.kiosk
+            .borrow()
+            .as_ref()
+            .map_or(false, |k| url.scheme() == shell_pages::SCHEME || !k.allows(&url));
+        if kiosk_denies {
+            info!("Kiosk: navigation to {} denied", url);
+            chan.send(false).ok();
+        } else if url.scheme() == shell_pages::SCHEME {
+            let event = ServoEvent::ShellUrl(id, url, true);
             self.event_queue.borrow_mut().push(event);
             chan.send(false).ok();
-        } else if url.as_str() == SERVO_ISSUE_ALIAS {
-            let event = ServoEvent::OpenInDefaultBrowser(SERVO_ISSUE_URL);
+        } else if self.blocker.borrow().is_blocked(&url) {
+            let event = ServoEvent::NavigationBlocked(id, url);
             self.event_queue.borrow_mut().push(event);
             chan.send(false).ok();
         } else {
@@ -417,6 +705,18 @@ impl WindowMethods for ServoCallbacks {
     }
 
     fn present(&self) {
+        // Read the frame before it's swapped, the back buffer is undefined afterward
+        if let Some(request) = self.capture.borrow_mut().take() {
+            let frame = self.read_frame();
+            let event = match request {
+                CaptureRequest::Screenshot(path) => {
+                    let result = frame.write_png(&path);
+                    ServoEvent::ScreenshotTaken(path, result)
+                }
+                CaptureRequest::Frame => ServoEvent::FrameCaptured(frame),
+            };
+            self.event_queue.borrow_mut().push(event);
+        }
         self.view.swap_buffers();
     }
 
@@ -426,7 +726,16 @@ impl WindowMethods for ServoCallbacks {
             .push(ServoEvent::TitleChanged(id, title));
     }
 
-    fn status(&self, _id: BrowserId, status: Option<String>) {
+    fn status(&self, id: BrowserId, status: Option<String>) {
+        // Hovered links are absolute URLs, anything else is not a link
+        *self.hovered_link.borrow_mut() = status
+            .as_ref()
+            .and_then(|status| ServoUrl::parse(status).ok())
+            .and_then(|url| if LINK_SCHEMES.contains(&url.scheme()) {
+                          Some((id, url))
+                      } else {
+                          None
+                      });
         self.event_queue
             .borrow_mut()
             .push(ServoEvent::StatusChanged(status));
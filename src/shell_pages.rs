@@ -0,0 +1,521 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! `servoshell://` URLs. Servo can't load them, so navigations to them are
+//! denied in `allow_navigation` (or intercepted when typed), and the shell
+//! loads a page it generated instead, written to a temporary directory.
+//!
+//! Pages talk back to the shell by navigating to
+//! `servoshell://command/NAME?ARGS`. Commands are only accepted from the
+//! shell page offering them, never from web content or the URL bar.
+
+use logs::{ShellLog, ShellLogs};
+use serde_json;
+use servo::ServoUrl;
+use serde_json::Value;
+use state::{AppState, WindowState};
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use storage;
+use traits::view::DrawableGeometry;
+
+pub const SCHEME: &'static str = "servoshell";
+
+const SHELL_ISSUE_URL: &'static str = "http://github.com/paulrouget/servoshell/issues/new";
+const SERVO_ISSUE_URL: &'static str = "http://github.com/servo/servo/issues/new";
+
+// Static, built in: it doesn't depend on where the resources are
+const HOME_PAGE: &'static str = include_str!("../shell_resources/home.html");
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Route {
+    Home,
+    History,
+    Bookmarks,
+    Settings,
+    Logs,
+    Version,
+}
+
+const ROUTES: &'static [(&'static str, Route, &'static str)] =
+    &[("home", Route::Home, "Home"),
+      ("history", Route::History, "History"),
+      ("bookmarks", Route::Bookmarks, "Bookmarks"),
+      ("settings", Route::Settings, "Settings"),
+      ("logs", Route::Logs, "Logs"),
+      ("version", Route::Version, "Version")];
+
+impl Route {
+    fn name(&self) -> &'static str {
+        ROUTES.iter().find(|r| r.1 == *self).unwrap().0
+    }
+
+    fn title(&self) -> &'static str {
+        ROUTES.iter().find(|r| r.1 == *self).unwrap().2
+    }
+
+    pub fn url(&self) -> String {
+        format!("{}://{}", SCHEME, self.name())
+    }
+}
+
+/// What pages can ask the shell to do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PageCommand {
+    ClearHistory,
+    RemoveBookmark(String),
+    ToggleDarkTheme,
+    // What servoshell://version shows
+    CopyDiagnostics,
+    CopyLogs,
+}
+
+impl PageCommand {
+    /// The page offering the command.
+    pub fn route(&self) -> Route {
+        match *self {
+            PageCommand::ClearHistory => Route::History,
+            PageCommand::RemoveBookmark(_) => Route::Bookmarks,
+            PageCommand::ToggleDarkTheme => Route::Settings,
+            PageCommand::CopyDiagnostics => Route::Version,
+            PageCommand::CopyLogs => Route::Logs,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShellRequest {
+    Page(Route),
+    Command(PageCommand),
+    // Opened in the default browser
+    External(&'static str),
+}
+
+pub fn is_shell_url(url: &str) -> bool {
+    url.starts_with(&format!("{}://", SCHEME))
+}
+
+pub fn parse(url: &ServoUrl) -> Result<ShellRequest, String> {
+    if url.scheme() != SCHEME {
+        return Err(format!("Not a {} URL: {}", SCHEME, url));
+    }
+    let host = url.host_str().unwrap_or("");
+    let path = url.path().trim_matches('/');
+    match (host, path) {
+        ("issue", "servo") => Ok(ShellRequest::External(SERVO_ISSUE_URL)),
+        ("issue", "servoshell") => Ok(ShellRequest::External(SHELL_ISSUE_URL)),
+        ("command", name) => parse_command(url, name).map(ShellRequest::Command),
+        (name, "") => {
+            ROUTES
+                .iter()
+                .find(|r| r.0 == name)
+                .map(|r| ShellRequest::Page(r.1))
+                .ok_or_else(|| format!("Unknown page: {}", url))
+        }
+        _ => Err(format!("Unknown page: {}", url)),
+    }
+}
+
+fn parse_command(url: &ServoUrl, name: &str) -> Result<PageCommand, String> {
+    let arg = |key: &str| {
+        url.as_url()
+            .query_pairs()
+            .find(|&(ref k, _)| &**k == key)
+            .map(|(_, v)| v.into_owned())
+            .ok_or_else(|| format!("Command {}: missing `{}`", name, key))
+    };
+    match name {
+        "clear-history" => Ok(PageCommand::ClearHistory),
+        "remove-bookmark" => arg("url").map(PageCommand::RemoveBookmark),
+        "toggle-dark-theme" => Ok(PageCommand::ToggleDarkTheme),
+        "copy-diagnostics" => Ok(PageCommand::CopyDiagnostics),
+        "copy-logs" => Ok(PageCommand::CopyLogs),
+        _ => Err(format!("Unknown command: {}", name)),
+    }
+}
+
+/// What the pages show.
+pub struct PageContext<'a> {
+    pub app_state: &'a AppState,
+    pub win_state: &'a WindowState,
+    pub logs: &'a [ShellLog],
+    pub config_path: &'a Path,
+    pub shell_version: &'a str,
+    pub servo_version: String,
+    pub resources_path: &'a Path,
+    // Platform backend
+    pub backend: &'static str,
+    pub geometry: DrawableGeometry,
+    // Non-default prefs
+    pub prefs: Vec<(String, String)>,
+}
+
+pub struct ShellPages {
+    // Ours only: pages can send commands
+    dir: PathBuf,
+    // As last shown by servoshell://version
+    diagnostics: RefCell<String>,
+    // ShellLogs::total when logs.js was written
+    logs_written: Cell<u64>,
+}
+
+impl ShellPages {
+    pub fn new() -> Result<ShellPages, String> {
+        let dir = storage::create_temp_dir("servoshell-pages-")?;
+        Ok(ShellPages {
+               dir,
+               diagnostics: RefCell::new(String::new()),
+               logs_written: Cell::new(0),
+           })
+    }
+
+    /// Generate the page, and return the URL Servo should load.
+    pub fn open(&self, route: Route, context: &PageContext) -> Result<ServoUrl, String> {
+        match route {
+            Route::Version => *self.diagnostics.borrow_mut() = diagnostics(context),
+            Route::Logs => self.write_logs(context.logs)?,
+            _ => {}
+        }
+        let page = match route {
+            Route::Home => HOME_PAGE.to_owned(),
+            _ => render(route, context),
+        };
+        let path = self.dir.join(format!("{}.html", route.name()));
+        File::create(&path)
+            .and_then(|mut file| file.write_all(page.as_bytes()))
+            .map_err(|e| format!("Can't write {}: {}", path.display(), e))?;
+        ServoUrl::from_file_path(&path).map_err(|()| format!("Invalid path {}", path.display()))
+    }
+
+    /// servoshell://logs loads logs.js again and again. Rewritten only if
+    /// there are new logs.
+    pub fn update_logs(&self, logs: &ShellLogs) {
+        let total = logs.total();
+        if total == self.logs_written.get() {
+            return;
+        }
+        self.logs_written.set(total);
+        if let Err(err) = self.write_logs(&logs.peek()) {
+            warn!("{}", err);
+        }
+    }
+
+    fn write_logs(&self, logs: &[ShellLog]) -> Result<(), String> {
+        let entries: Vec<String> = logs.iter().map(|log| log.to_json()).collect();
+        // Line terminators for JavaScript, not for JSON
+        let script = format!("updateLogs([{}]);\n", entries.join(",\n"))
+            .replace('\u{2028}', "\\u2028")
+            .replace('\u{2029}', "\\u2029");
+        let path = self.dir.join("logs.js");
+        File::create(&path)
+            .and_then(|mut file| file.write_all(script.as_bytes()))
+            .map_err(|e| format!("Can't write {}: {}", path.display(), e))
+    }
+
+    /// For bug reports.
+    pub fn diagnostics(&self) -> String {
+        self.diagnostics.borrow().clone()
+    }
+
+    /// The servoshell:// URL of a generated page. Shown instead of the
+    /// file URL.
+    pub fn shell_url(&self, url: &ServoUrl) -> Option<ServoUrl> {
+        let path = url.to_file_path().ok()?;
+        if path.parent() != Some(self.dir.as_path()) {
+            return None;
+        }
+        let name = path.file_stem()?.to_str()?;
+        let route = ROUTES.iter().find(|r| r.0 == name)?.1;
+        ServoUrl::parse(&route.url()).ok()
+    }
+}
+
+impl Drop for ShellPages {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// For command arguments.
+fn encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+                 b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' | b'_' | b'.' | b'~' => {
+                     (b as char).to_string()
+                 }
+                 _ => format!("%{:02X}", b),
+             })
+        .collect()
+}
+
+fn command_url(name: &str, args: &[(&str, &str)]) -> String {
+    let query: Vec<String> = args.iter()
+        .map(|&(k, v)| format!("{}={}", k, encode(v)))
+        .collect();
+    if query.is_empty() {
+        format!("{}://command/{}", SCHEME, name)
+    } else {
+        format!("{}://command/{}?{}", SCHEME, name, query.join("&"))
+    }
+}
+
+fn render(route: Route, context: &PageContext) -> String {
+    let body = match route {
+        Route::Home => String::new(),
+        Route::History => render_history(context),
+        Route::Bookmarks => render_bookmarks(context),
+        Route::Settings => render_settings(context),
+        Route::Logs => render_logs(),
+        Route::Version => render_version(context),
+    };
+    let nav: Vec<String> = ROUTES
+        .iter()
+        .map(|r| format!("<a href=\"{}\">{}</a>", r.1.url(), r.2))
+        .collect();
+    let theme = if context.app_state.dark_theme {
+        "body { background: #222; color: #eee; } a { color: #8af; }"
+    } else {
+        ""
+    };
+    format!("<!DOCTYPE html>
+<meta charset=\"utf8\">
+<title>{title}</title>
+<style>
+  body {{ margin: 30px 50px; font-family: Helvetica; font-weight: lighter; }}
+  nav a {{ margin-right: 1em; }}
+  td {{ padding: 2px 1em 2px 0; vertical-align: top; }}
+  pre {{ white-space: pre-wrap; }}
+  {theme}
+</style>
+<nav>{nav}</nav>
+<h1>{title}</h1>
+{body}
+",
+            title = route.title(),
+            theme = theme,
+            nav = nav.join(""),
+            body = body)
+}
+
+fn render_history(context: &PageContext) -> String {
+    let history = &context.app_state.history;
+    if history.is_empty() {
+        return "<p>No history.</p>".to_owned();
+    }
+    let rows: Vec<String> = history
+        .iter()
+        .rev()
+        .map(|entry| {
+                 format!("<tr><td><a href=\"{url}\">{title}</a></td><td>{url}</td></tr>",
+                         url = escape(&entry.url),
+                         title = escape(entry.title.as_ref().unwrap_or(&entry.url)))
+             })
+        .collect();
+    format!("<p><a href=\"{}\">Clear history</a></p><table>{}</table>",
+            command_url("clear-history", &[]),
+            rows.join("\n"))
+}
+
+fn render_bookmarks(context: &PageContext) -> String {
+    let bookmarks = &context.app_state.bookmarks;
+    if bookmarks.is_empty() {
+        return "<p>No bookmarks.</p>".to_owned();
+    }
+    let rows: Vec<String> = bookmarks
+        .iter()
+        .map(|bookmark| {
+            format!("<tr><td><a href=\"{url}\">{title}</a></td><td>{url}</td>\
+                     <td><a href=\"{remove}\">Remove</a></td></tr>",
+                    url = escape(&bookmark.url),
+                    title = escape(bookmark.title.as_ref().unwrap_or(&bookmark.url)),
+                    remove = escape(&command_url("remove-bookmark", &[("url", &bookmark.url)])))
+        })
+        .collect();
+    format!("<table>{}</table>", rows.join("\n"))
+}
+
+fn render_settings(context: &PageContext) -> String {
+    let config = serde_json::to_string_pretty(&context.app_state.config).unwrap_or_default();
+    let debug_options = serde_json::to_string_pretty(&context.win_state.debug_options)
+        .unwrap_or_default();
+    format!("<p><a href=\"{toggle}\">Toggle dark theme</a></p>
+<h2>Settings</h2>
+<p>Edit <code>{path}</code>, changes are applied right away.</p>
+<pre>{config}</pre>
+<h2>Debug options</h2>
+<pre>{debug_options}</pre>",
+            toggle = command_url("toggle-dark-theme", &[]),
+            path = escape(&context.config_path.display().to_string()),
+            config = escape(&config),
+            debug_options = escape(&debug_options))
+}
+
+fn render_logs() -> String {
+    format!("<p>
+  <select id=\"level\">
+    <option value=\"5\">All levels</option>
+    <option value=\"1\">Error</option>
+    <option value=\"2\">Warn and above</option>
+    <option value=\"3\">Info and above</option>
+    <option value=\"4\">Debug and above</option>
+  </select>
+  <input id=\"target\" placeholder=\"Target\">
+  <input id=\"search\" placeholder=\"Search\">
+  <button onclick=\"location.href = '{}'\">Copy all</button>
+</p>
+<p id=\"count\"></p>
+<table id=\"logs\"></table>
+<script>{}</script>
+<script src=\"logs.js\"></script>",
+            command_url("copy-logs", &[]),
+            LOGS_SCRIPT)
+}
+
+// Filters the logs of logs.js, loaded again every second.
+const LOGS_SCRIPT: &'static str = "
+  var LEVELS = {ERROR: 1, WARN: 2, INFO: 3, DEBUG: 4, TRACE: 5};
+  var logs = [];
+  function updateLogs(entries) {
+    logs = entries;
+    show();
+  }
+  function show() {
+    var level = Number(document.getElementById('level').value);
+    var target = document.getElementById('target').value;
+    var search = document.getElementById('search').value.toLowerCase();
+    var table = document.getElementById('logs');
+    while (table.firstChild) {
+      table.removeChild(table.firstChild);
+    }
+    var shown = 0;
+    logs.forEach(function(log) {
+      if (LEVELS[log.level] > level ||
+          log.target.indexOf(target) != 0 ||
+          log.message.toLowerCase().indexOf(search) == -1) {
+        return;
+      }
+      var row = document.createElement('tr');
+      [log.level, log.target, log.message].forEach(function(text) {
+        var cell = document.createElement('td');
+        cell.textContent = text;
+        row.appendChild(cell);
+      });
+      table.appendChild(row);
+      shown++;
+    });
+    document.getElementById('count').textContent = shown + ' of ' + logs.length + ' logs';
+  }
+  ['level', 'target', 'search'].forEach(function(id) {
+    document.getElementById(id).addEventListener('input', show);
+    document.getElementById(id).addEventListener('change', show);
+  });
+  setInterval(function() {
+    var script = document.createElement('script');
+    script.src = 'logs.js?' + Date.now();
+    script.onload = script.onerror = function() {
+      document.body.removeChild(script);
+    };
+    document.body.appendChild(script);
+  }, 1000);
+";
+
+/// Everything a bug report needs, as plain text.
+fn diagnostics(context: &PageContext) -> String {
+    let geometry = &context.geometry;
+    let debug_options: Vec<String> = match serde_json::to_value(&context.win_state.debug_options) {
+        Ok(Value::Object(options)) => {
+            options
+                .into_iter()
+                .filter(|&(_, ref enabled)| *enabled == Value::Bool(true))
+                .map(|(name, _)| name)
+                .collect()
+        }
+        _ => vec![],
+    };
+    let prefs: Vec<String> = context
+        .prefs
+        .iter()
+        .map(|&(ref name, ref value)| format!("  {} = {}", name, value))
+        .collect();
+    format!("ServoShell: {}
+Servo: {}
+Resources: {}
+Platform: {} ({})
+HiDPI factor: {}
+View size: {}x{}
+Margins (top, right, bottom, left): {:?}
+Position: {:?}
+Debug options: {}
+Prefs:{}{}
+",
+            context.shell_version,
+            context.servo_version,
+            context.resources_path.display(),
+            context.backend,
+            env::consts::OS,
+            geometry.hidpi_factor,
+            geometry.view_size.0,
+            geometry.view_size.1,
+            geometry.margins,
+            geometry.position,
+            if debug_options.is_empty() {
+                "none".to_owned()
+            } else {
+                debug_options.join(", ")
+            },
+            if prefs.is_empty() { " default" } else { "\n" },
+            prefs.join("\n"))
+}
+
+fn render_version(context: &PageContext) -> String {
+    format!("<p><button onclick=\"location.href = '{}'\">Copy as text</button></p>\
+             <pre>{}</pre>",
+            command_url("copy-diagnostics", &[]),
+            escape(&diagnostics(context)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, PageCommand, Route, ShellRequest};
+    use servo::ServoUrl;
+
+    fn parse_str(url: &str) -> Result<ShellRequest, String> {
+        parse(&ServoUrl::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_str("servoshell://history"),
+                   Ok(ShellRequest::Page(Route::History)));
+        assert_eq!(parse_str("servoshell://command/remove-bookmark?url=http%3A%2F%2Fa.b"),
+                   Ok(ShellRequest::Command(PageCommand::RemoveBookmark("http://a.b"
+                                                                            .to_owned()))));
+        assert!(parse_str("servoshell://command/remove-bookmark").is_err());
+        assert!(parse_str("servoshell://command/format-disk").is_err());
+        assert!(parse_str("servoshell://nope").is_err());
+        assert!(parse_str("https://servo.org").is_err());
+    }
+
+    #[test]
+    fn test_command_routes() {
+        let command = |url| match parse_str(url) {
+            Ok(ShellRequest::Command(command)) => command,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(command("servoshell://command/clear-history").route(), Route::History);
+        assert_eq!(command("servoshell://command/toggle-dark-theme").route(),
+                   Route::Settings);
+        assert_eq!(command("servoshell://command/copy-logs").route(), Route::Logs);
+    }
+}
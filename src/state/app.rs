@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use config::Config;
 use servo::ServoCursor;
 
 #[derive(Clone, PartialEq, Deserialize, Serialize)]
@@ -9,6 +10,25 @@ pub struct AppState {
     pub current_window_index: Option<usize>,
     pub dark_theme: bool,
     pub cursor: ServoCursor,
+    pub history: Vec<HistoryEntry>,
+    pub bookmarks: Vec<Bookmark>,
+    pub config: Config,
+    // Sites content blocking is disabled for
+    pub blocking_exceptions: Vec<String>,
+    // Fullscreen, no shell shortcuts
+    pub kiosk: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Bookmark {
+    pub url: String,
+    pub title: Option<String>,
 }
 
 impl AppState {
@@ -17,6 +37,11 @@ impl AppState {
             current_window_index: None,
             dark_theme: false,
             cursor: ServoCursor::Default,
+            history: Vec::new(),
+            bookmarks: Vec::new(),
+            config: Config::default(),
+            blocking_exceptions: Vec::new(),
+            kiosk: false,
         }
     }
 }
@@ -17,6 +17,10 @@ pub struct BrowserState {
     pub can_go_forward: bool,
     pub is_loading: bool,
     pub urlbar_focused: bool,
+    // Navigations denied by the content blocker
+    pub blocked_count: usize,
+    // Sent by Servo. The same for every tab, see user_agent.rs
+    pub user_agent: String,
     // FIXME:
     // creation_timestamp
 }
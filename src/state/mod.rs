@@ -9,6 +9,7 @@ mod tabs;
 mod window;
 
 pub use self::state::{DiffKey, ChangeType, State};
-pub use self::app::AppState;
+pub use self::app::{AppState, Bookmark, HistoryEntry};
 pub use self::browser::{BrowserState, DeadBrowserState};
-pub use self::window::WindowState;
+pub use self::window::{CommandPaletteState, ContextMenuItem, ContextMenuState, DebugOptions,
+                       PaletteMatch, PaletteMatchKind, TabTitleFormat, WindowState};
@@ -91,6 +91,17 @@ pub enum DiffKey {
     wr_profiler,
     wr_texture_cache_debug,
     wr_render_target_debug,
+    command_palette,
+    query,
+    matches,
+    history,
+    bookmarks,
+    context_menu,
+    config,
+    tab_title,
+    blocked_count,
+    blocking_exceptions,
+    user_agent,
 }
 
 impl DiffKey {
@@ -128,6 +139,17 @@ impl DiffKey {
                     "wr_profiler" => DiffKey::wr_profiler,
                     "wr_texture_cache_debug" => DiffKey::wr_texture_cache_debug,
                     "wr_render_target_debug" => DiffKey::wr_render_target_debug,
+                    "command_palette" => DiffKey::command_palette,
+                    "query" => DiffKey::query,
+                    "matches" => DiffKey::matches,
+                    "history" => DiffKey::history,
+                    "bookmarks" => DiffKey::bookmarks,
+                    "context_menu" => DiffKey::context_menu,
+                    "config" => DiffKey::config,
+                    "tab_title" => DiffKey::tab_title,
+                    "blocked_count" => DiffKey::blocked_count,
+                    "blocking_exceptions" => DiffKey::blocking_exceptions,
+                    "user_agent" => DiffKey::user_agent,
                     s => DiffKey::Unknown(s.to_owned()),
                 }
             }
@@ -2,7 +2,9 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use super::BrowserState;
 use super::tabs::TabsState;
+use traits::window::WindowCommand;
 
 #[derive(Clone, PartialEq, Deserialize, Serialize)]
 pub struct WindowState {
@@ -13,6 +15,13 @@ pub struct WindowState {
     pub status: Option<String>,
     pub options_open: bool,
     pub title: String,
+    pub command_palette: Option<CommandPaletteState>,
+    pub context_menu: Option<ContextMenuState>,
+    pub tab_title: TabTitleFormat,
+    // Nothing is written to disk
+    pub private: bool,
+    // Size of the view when the window is created, in logical pixels
+    pub inner_size: (u32, u32),
 }
 
 impl WindowState {
@@ -24,21 +33,35 @@ impl WindowState {
             status: None,
             options_open: false,
             title: "ServoShell".to_owned(),
-            debug_options: DebugOptions {
-                show_fragment_borders: false,
-                parallel_display_list_building: false,
-                show_parallel_layout: false,
-                convert_mouse_to_touch: false,
-                show_tiles_borders: false,
-                wr_profiler: false,
-                wr_texture_cache_debug: false,
-                wr_render_target_debug: false,
-            },
+            command_palette: None,
+            context_menu: None,
+            debug_options: DebugOptions::new(),
+            tab_title: TabTitleFormat::default(),
+            private: false,
+            inner_size: (1024, 768),
         }
     }
 }
 
-#[derive(Clone, PartialEq, Deserialize, Serialize)]
+/// How tabs are shown when the UI has no tab bar. `{selected}`, `{title}`,
+/// `{url}` and `{loading}` are replaced. Titles are cut to `max_length`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TabTitleFormat {
+    pub template: String,
+    pub max_length: usize,
+}
+
+impl Default for TabTitleFormat {
+    fn default() -> TabTitleFormat {
+        TabTitleFormat {
+            template: "{selected} {title} {loading}".to_owned(),
+            max_length: 15,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct DebugOptions {
     pub show_fragment_borders: bool,
     pub parallel_display_list_building: bool,
@@ -51,3 +74,97 @@ pub struct DebugOptions {
     pub wr_texture_cache_debug: bool,
     pub wr_render_target_debug: bool,
 }
+
+impl DebugOptions {
+    pub fn new() -> DebugOptions {
+        DebugOptions {
+            show_fragment_borders: false,
+            parallel_display_list_building: false,
+            show_parallel_layout: false,
+            convert_mouse_to_touch: false,
+            show_tiles_borders: false,
+            wr_profiler: false,
+            wr_texture_cache_debug: false,
+            wr_render_target_debug: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct CommandPaletteState {
+    pub query: String,
+    pub matches: Vec<PaletteMatch>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum PaletteMatchKind {
+    Command,
+    Tab,
+    Bookmark,
+    History,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PaletteMatch {
+    pub kind: PaletteMatchKind,
+    pub label: String,
+    pub command: WindowCommand,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum ContextMenuItem {
+    OpenLinkInNewTab,
+    CopyLinkAddress,
+    OpenLinkInDefaultBrowser,
+    NavigateBack,
+    NavigateForward,
+    Reload,
+    ViewPageSource,
+}
+
+impl ContextMenuItem {
+    pub fn label(&self) -> &'static str {
+        match *self {
+            ContextMenuItem::OpenLinkInNewTab => "Open Link in New Tab",
+            ContextMenuItem::CopyLinkAddress => "Copy Link Address",
+            ContextMenuItem::OpenLinkInDefaultBrowser => "Open Link in Default Browser",
+            ContextMenuItem::NavigateBack => "Back",
+            ContextMenuItem::NavigateForward => "Forward",
+            ContextMenuItem::Reload => "Reload",
+            ContextMenuItem::ViewPageSource => "View Page Source",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ContextMenuState {
+    // View coordinates, in device pixels
+    pub x: i32,
+    pub y: i32,
+    pub link: Option<String>,
+    pub items: Vec<ContextMenuItem>,
+}
+
+impl ContextMenuState {
+    // FIXME: Servo doesn't tell us what is under the cursor. We only know
+    // about links, via the status. No image specific items for now.
+    pub fn new(x: i32, y: i32, link: Option<String>, browser: &BrowserState) -> ContextMenuState {
+        let mut items = vec![];
+        if link.is_some() {
+            items.push(ContextMenuItem::OpenLinkInNewTab);
+            items.push(ContextMenuItem::CopyLinkAddress);
+            items.push(ContextMenuItem::OpenLinkInDefaultBrowser);
+        }
+        if browser.can_go_back {
+            items.push(ContextMenuItem::NavigateBack);
+        }
+        if browser.can_go_forward {
+            items.push(ContextMenuItem::NavigateForward);
+        }
+        items.push(ContextMenuItem::Reload);
+        if browser.url.is_some() {
+            items.push(ContextMenuItem::ViewPageSource);
+        }
+        ContextMenuState { x, y, link, items }
+    }
+}
@@ -0,0 +1,115 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where the shell keeps its profiles, in the platform's config directory:
+/// `$XDG_CONFIG_HOME/servoshell`, or `~/.config/servoshell`.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub fn config_dir() -> Option<PathBuf> {
+    // Relative paths are invalid, per the XDG spec
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .and_then(|dir| if dir.is_absolute() { Some(dir) } else { None })
+        .or_else(|| env::home_dir().map(|home| home.join(".config")))
+        .map(|dir| dir.join("servoshell"))
+}
+
+/// `~/Library/Application Support/servoshell`.
+#[cfg(target_os = "macos")]
+pub fn config_dir() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join("Library/Application Support/servoshell"))
+}
+
+/// `%APPDATA%\servoshell`.
+#[cfg(target_os = "windows")]
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("servoshell"))
+}
+
+/// Read a JSON file. None if the file doesn't exist or can't be parsed.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    if !path.exists() {
+        return None;
+    }
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            warn!("Can't open {:?}: {}", path, err);
+            return None;
+        }
+    };
+    match serde_json::from_reader(file) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("Can't parse {:?}: {}", path, err);
+            None
+        }
+    }
+}
+
+/// Write a JSON file. Parent directories are created if needed.
+pub fn save<T: Serialize>(path: &Path, value: &T) {
+    if let Some(dir) = path.parent() {
+        if let Err(err) = fs::create_dir_all(dir) {
+            warn!("Can't create {:?}: {}", dir, err);
+            return;
+        }
+    }
+    let result = File::create(path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| serde_json::to_writer_pretty(file, value).map_err(|e| e.to_string()));
+    if let Err(err) = result {
+        warn!("Can't write {:?}: {}", path, err);
+    }
+}
+
+/// A new directory in the temporary directory, with a random name starting
+/// with `prefix`. Only the user can read it. Never an existing directory,
+/// that could have been made by someone else.
+pub fn create_temp_dir(prefix: &str) -> Result<PathBuf, String> {
+    for _ in 0..10 {
+        let dir = env::temp_dir().join(format!("{}{:016x}", prefix, random()));
+        match create_private_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(ref err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(format!("Can't create {}: {}", dir.display(), err)),
+        }
+    }
+    Err(format!("Can't create a temporary directory for {}", prefix))
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(dir)
+}
+
+// The temporary directory is already per user
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::DirBuilder::new().create(dir)
+}
+
+/// Hard to guess, the hasher keys come from the OS. Not for cryptography.
+fn random() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(process::id());
+    hasher.write_u32(nanos);
+    hasher.finish()
+}
@@ -27,6 +27,8 @@ pub trait AppMethods {
     fn new<'a>(state: &AppState) -> Result<Self, &'a str> where Self: Sized;
     fn new_window<'a>(&self, state: &WindowState) -> Result<Box<WindowMethods>, &'a str>;
     fn get_resources_path() -> Option<PathBuf>;
+    /// The platform backend, for diagnostics.
+    fn get_name() -> &'static str;
     fn render(&self, diff: Vec<ChangeType>, state: &AppState);
     fn get_events(&self) -> Vec<AppEvent>;
     fn run<T>(&self, callback: T) where T: FnMut();
@@ -20,7 +20,7 @@ pub struct DrawableGeometry {
 
 /// View events
 // FIXME: why not Servo events again?
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum ViewEvent {
     GeometryDidChange,
     MouseWheel(MouseScrollDelta, TouchPhase),
@@ -29,27 +29,27 @@ pub enum ViewEvent {
     KeyEvent(Option<char>, Key, KeyState, KeyModifiers),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum TouchPhase {
     Started,
     Moved,
     Ended,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum ElementState {
     Pressed,
     Released,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum MouseButton {
     Left,
     Right,
     Middle,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum MouseScrollDelta {
     LineDelta(f32, f32),
     PixelDelta(f32, f32),
@@ -60,6 +60,7 @@ pub trait ViewMethods {
     fn update_drawable(&self);
     fn enter_fullscreen(&self);
     fn exit_fullscreen(&self);
+    fn set_inner_size(&self, width: u32, height: u32);
     fn set_live_resize_callback(&self, callback: &FnMut());
     fn gl(&self) -> Rc<gl::Gl>;
     fn get_events(&self) -> Vec<ViewEvent>;
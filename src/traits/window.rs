@@ -4,9 +4,10 @@
 
 #![cfg_attr(any(feature = "force-glutin", not(target_os = "macos")), allow(dead_code))]
 
-use state::{ChangeType, WindowState};
+use state::{ChangeType, ContextMenuItem, WindowState};
 use traits::view::ViewMethods;
 use servo::EventLoopWaker;
+use std::mem;
 use std::rc::Rc;
 use logs::ShellLog;
 
@@ -19,37 +20,98 @@ pub enum WindowEvent {
     WillClose,
     OptionsClosed,
     UrlbarFocusChanged(bool),
+    CommandPaletteQueryChanged(String),
+    CommandPaletteSubmit(usize),
+    CommandPaletteClosed,
+    ContextMenuItemSelected(ContextMenuItem),
+    ContextMenuClosed,
     DoCommand(WindowCommand),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub enum WindowCommand {
-    Reload,
-    Stop,
-    NavigateBack,
-    NavigateForward,
-    OpenLocation,
-    OpenInDefaultBrowser,
-    ZoomIn,
-    ZoomOut,
-    ZoomToActualSize,
-    ToggleSidebar,
-    NewTab,
-    CloseTab,
-    NextTab,
-    PrevTab,
-    SelectTab(usize),
-    ShowOptions,
-    Load(String),
-    ToggleOptionShowLogs,
-    ToggleOptionFragmentBorders,
-    ToggleOptionParallelDisplayListBuidling,
-    ToggleOptionShowParallelLayout,
-    ToggleOptionConvertMouseToTouch,
-    ToggleOptionTileBorders,
-    ToggleOptionWRProfiler,
-    ToggleOptionWRTextureCacheDebug,
-    ToggleOptionWRTargetDebug,
+// Is the command listed by the command palette
+macro_rules! listed {
+    (palette) => { true };
+    (hidden) => { false };
+}
+
+/// Declares `WindowCommand` and, from the same list, the table behind
+/// `all()` and `name()`. Commands taking an argument give the value the
+/// palette runs them with.
+macro_rules! window_commands {
+    ($($variant:ident $(($arg:ty = $default:expr))* => $name:expr, $palette:ident;)*) => {
+        #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+        pub enum WindowCommand {
+            $($variant $(($arg))*,)*
+        }
+
+        impl WindowCommand {
+            fn table() -> Vec<(WindowCommand, &'static str, bool)> {
+                vec![$((WindowCommand::$variant $(($default))*, $name, listed!($palette)),)*]
+            }
+        }
+    }
+}
+
+window_commands! {
+    Reload => "Reload Page", palette;
+    Stop => "Stop Loading", palette;
+    NavigateBack => "Navigate Back", palette;
+    NavigateForward => "Navigate Forward", palette;
+    OpenLocation => "Open Location", palette;
+    OpenInDefaultBrowser => "Open in Default Browser", palette;
+    ZoomIn => "Zoom In", palette;
+    ZoomOut => "Zoom Out", palette;
+    ZoomToActualSize => "Zoom to Actual Size", palette;
+    ToggleSidebar => "Toggle Sidebar", palette;
+    NewTab => "New Tab", palette;
+    CloseTab => "Close Tab", palette;
+    NextTab => "Next Tab", palette;
+    PrevTab => "Previous Tab", palette;
+    SelectTab(usize = 0) => "Select Tab", hidden;
+    ShowOptions => "Show Options", palette;
+    Load(String = String::new()) => "Load URL", hidden;
+    ToggleOptionShowLogs => "Toggle Shell Logs", palette;
+    // servoshell://logs, in a new tab
+    OpenLogs => "Open Shell Logs Page", palette;
+    // Servo reads these four once, when it starts
+    ToggleOptionFragmentBorders => "Debug: Toggle Fragment Borders (After Restart)", palette;
+    ToggleOptionParallelDisplayListBuidling =>
+        "Debug: Toggle Parallel Display List Building (After Restart)", palette;
+    ToggleOptionShowParallelLayout => "Debug: Toggle Parallel Layout (After Restart)", palette;
+    ToggleOptionConvertMouseToTouch => "Debug: Toggle Mouse to Touch", palette;
+    ToggleOptionTileBorders => "Debug: Toggle Tile Borders (After Restart)", palette;
+    ToggleOptionWRProfiler => "Debug: Toggle WebRender Profiler", palette;
+    ToggleOptionWRTextureCacheDebug => "Debug: Toggle WebRender Texture Cache", palette;
+    ToggleOptionWRTargetDebug => "Debug: Toggle WebRender Render Targets", palette;
+    ShowCommandPalette => "Show Command Palette", palette;
+    BookmarkPage => "Bookmark Page", palette;
+    CopyUrl => "Copy Page Address", palette;
+    PasteAndGo => "Paste and Go", palette;
+    // Path of the PNG file. An empty string saves it in the home directory
+    Screenshot(String = String::new()) => "Take Screenshot", palette;
+    // For the site of the current tab
+    ToggleContentBlocking => "Toggle Content Blocking for This Site", palette;
+}
+
+impl WindowCommand {
+    /// Commands listed by the command palette.
+    pub fn all() -> Vec<WindowCommand> {
+        WindowCommand::table()
+            .into_iter()
+            .filter(|&(_, _, listed)| listed)
+            .map(|(command, _, _)| command)
+            .collect()
+    }
+
+    /// Human-readable name of the command.
+    pub fn name(&self) -> &'static str {
+        let variant = mem::discriminant(self);
+        WindowCommand::table()
+            .into_iter()
+            .find(|&(ref command, _, _)| mem::discriminant(command) == variant)
+            .map(|(_, name, _)| name)
+            .unwrap()
+    }
 }
 
 pub trait WindowMethods {
@@ -59,3 +121,23 @@ pub trait WindowMethods {
     fn get_events(&self) -> Vec<WindowEvent>;
     fn append_logs(&self, logs: &Vec<ShellLog>);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::WindowCommand;
+
+    #[test]
+    fn every_command_has_a_name() {
+        for (command, name, _) in WindowCommand::table() {
+            assert_eq!(command.name(), name);
+        }
+    }
+
+    #[test]
+    fn palette_lists_marked_commands_with_their_default_argument() {
+        let all = WindowCommand::all();
+        assert!(all.contains(&WindowCommand::Reload));
+        assert!(all.contains(&WindowCommand::Screenshot(String::new())));
+        assert!(!all.iter().any(|c| c.name() == "Select Tab" || c.name() == "Load URL"));
+    }
+}
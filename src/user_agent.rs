@@ -0,0 +1,29 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! User agent presets, for `--user-agent`. Each `BrowserState` shows the
+//! user agent Servo sends.
+//!
+//! Only one user agent, for every tab: Servo reads it once, from its
+//! options. Embedder loads can't add headers either.
+//! FIXME: per tab and per site user agents, applied on the next navigation,
+//! need Servo to take one per browser or per load.
+
+const PRESETS: &'static [(&'static str, &'static str)] =
+    &[("desktop",
+       "Mozilla/5.0 (X11; Linux x86_64; rv:55.0) Servo/1.0 Firefox/55.0"),
+      ("android",
+       "Mozilla/5.0 (Android; Mobile; rv:55.0) Servo/1.0 Firefox/55.0"),
+      ("ios",
+       "Mozilla/5.0 (iPhone; CPU iPhone OS 11_0 like Mac OS X) AppleWebKit/604.1.38 \
+        (KHTML, like Gecko) Version/11.0 Mobile/15A372 Safari/604.1")];
+
+/// The user agent string of a preset, or the value itself.
+pub fn resolve(value: &str) -> String {
+    PRESETS
+        .iter()
+        .find(|&&(name, _)| name == value)
+        .map_or(value, |&(_, user_agent)| user_agent)
+        .to_owned()
+}